@@ -13,7 +13,17 @@ pub struct Cli {
 pub enum Commands {
     #[command(about = "Find storage slot for a token")]
     FindStorageSlot(FindStorageSlotArgs),
-    SetBalance(SetBalanceArgs)
+    #[command(about = "Infer the balance storage slot by symbolically executing the bytecode, without RPC")]
+    FindBytecodeSlot(FindBytecodeSlotArgs),
+    #[command(about = "Resolve the balance slot from a storage layout and cross-check it with a trace")]
+    CheckStorageLayout(CheckStorageLayoutArgs),
+    SetBalance(SetBalanceArgs),
+    #[command(about = "Set the owner of an ERC-721 token id")]
+    SetNftOwner(SetNftOwnerArgs),
+    #[command(about = "Set the ERC-721 balance of a holder")]
+    SetNftBalance(SetNftBalanceArgs),
+    #[command(about = "Set the ERC-1155 / ERC-6909 balance of a holder for a token id")]
+    SetMultiTokenBalance(SetMultiTokenBalanceArgs),
 }
 
 #[derive(Args, Debug)]
@@ -24,10 +34,38 @@ pub struct FindStorageSlotArgs {
     pub rpc_url: Option<String>,
     #[arg(long, help = "RPC provider that will be used in Anvil fork. Default: None.")]
     pub fork_rpc_url: Option<String>,
+    #[arg(long, help = "Trace in-process with revm, so the RPC doesn't need debug_traceCall. Default: false.", default_value_t = false)]
+    pub local_trace: bool,
+    #[arg(long, help = "Don't fall back to poking every slot read by balanceOf, whose results only hold for the traced holder. Default: false.", default_value_t = false)]
+    pub no_poke_fallback: bool,
     #[arg(long, help = "True for unformatted output. Default: false.", default_value_t = false)]
     pub unformatted: bool,
 }
 
+#[derive(Args, Debug)]
+pub struct FindBytecodeSlotArgs {
+    #[arg(required = true, help = "Path to a file with the hex-encoded runtime bytecode.")]
+    pub hex_file: String,
+    #[arg(long, help = "Address the bytecode is deployed at, reported as the locator contract. Default: zero address.")]
+    pub contract: Option<String>,
+    #[arg(long, help = "Holder the symbolic balanceOf argument is matched as. Default: 0xb0b0..b0.")]
+    pub holder: Option<String>,
+    #[arg(long, help = "True for unformatted output. Default: false.", default_value_t = false)]
+    pub unformatted: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckStorageLayoutArgs {
+    #[arg(required = true, help = "Address of the token.")]
+    pub token: String,
+    #[arg(required = true, help = "Path to solc `storageLayout` JSON or a Foundry artifact (out/*.json).")]
+    pub layout_file: String,
+    #[arg(long, help = "RPC endpoint that supports debug_traceCall with struct trace. Default is http://localhost:8545.")]
+    pub rpc_url: Option<String>,
+    #[arg(long, help = "Trace in-process with revm, so the RPC doesn't need debug_traceCall. Default: false.", default_value_t = false)]
+    pub local_trace: bool,
+}
+
 #[derive(Args, Debug)]
 pub struct SetBalanceArgs {
     #[arg(required = true, help = "Address of the token to set balance for.")]
@@ -38,7 +76,53 @@ pub struct SetBalanceArgs {
     pub target_balance: f64,
     #[arg(long, help = "Set Anvil RPC endpoint. Default is http://localhost:8545.")]
     pub rpc_url: Option<String>,
+    #[arg(long, help = "Adjust totalSupply by the same amount as the balance. Default: false.", default_value_t = false)]
+    pub sync_total_supply: bool,
+    #[arg(long, help = "True for verbose output. Default: false.", default_value_t = false)]
+    pub verbose: bool,
+}
+
+
+#[derive(Args, Debug)]
+pub struct SetNftOwnerArgs {
+    #[arg(required = true, help = "Address of the ERC-721 collection.")]
+    pub token: String,
+    #[arg(required = true, help = "Token id to set the owner for, it has to be minted.")]
+    pub id: String,
+    #[arg(required = true, help = "Address of the new owner.")]
+    pub owner: String,
+    #[arg(long, help = "Set Anvil RPC endpoint. Default is http://localhost:8545.")]
+    pub rpc_url: Option<String>,
     #[arg(long, help = "True for verbose output. Default: false.", default_value_t = false)]
     pub verbose: bool,
 }
 
+#[derive(Args, Debug)]
+pub struct SetNftBalanceArgs {
+    #[arg(required = true, help = "Address of the ERC-721 collection.")]
+    pub token: String,
+    #[arg(required = true, help = "Address of the holder to set balance for.")]
+    pub holder: String,
+    #[arg(required = true, help = "Target number of tokens held.")]
+    pub target_balance: String,
+    #[arg(long, help = "Set Anvil RPC endpoint. Default is http://localhost:8545.")]
+    pub rpc_url: Option<String>,
+    #[arg(long, help = "True for verbose output. Default: false.", default_value_t = false)]
+    pub verbose: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SetMultiTokenBalanceArgs {
+    #[arg(required = true, help = "Address of the ERC-1155 / ERC-6909 token.")]
+    pub token: String,
+    #[arg(required = true, help = "Address of the holder to set balance for.")]
+    pub holder: String,
+    #[arg(required = true, help = "Token id to set balance for.")]
+    pub id: String,
+    #[arg(required = true, help = "Target balance in raw (fixed point) representation.")]
+    pub target_balance: String,
+    #[arg(long, help = "Set Anvil RPC endpoint. Default is http://localhost:8545.")]
+    pub rpc_url: Option<String>,
+    #[arg(long, help = "True for verbose output. Default: false.", default_value_t = false)]
+    pub verbose: bool,
+}
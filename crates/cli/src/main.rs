@@ -1,4 +1,3 @@
-mod balance_setter;
 mod config;
 mod utils;
 mod cmd;
@@ -20,18 +19,65 @@ pub async fn main() -> Result<()> {
                 cmd.tokens, 
                 cmd.rpc_url, 
                 cmd.fork_rpc_url,
+                cmd.local_trace,
+                !cmd.no_poke_fallback,
                 cmd.unformatted,
             ).await
         },
+        Commands::FindBytecodeSlot(cmd) => {
+            find_bytecode_slot(
+                cmd.hex_file, 
+                cmd.contract, 
+                cmd.holder,
+                cmd.unformatted,
+            )
+        },
+        Commands::CheckStorageLayout(cmd) => {
+            check_storage_layout(
+                cmd.token, 
+                cmd.layout_file, 
+                cmd.rpc_url,
+                cmd.local_trace,
+            ).await
+        },
         Commands::SetBalance(cmd) => {
             set_balance(
                 cmd.token, 
                 cmd.holder, 
                 cmd.target_balance, 
                 cmd.rpc_url, 
+                cmd.sync_total_supply,
                 cmd.verbose,
             ).await
-        }
+        },
+        Commands::SetNftOwner(cmd) => {
+            set_nft_owner(
+                cmd.token, 
+                cmd.id, 
+                cmd.owner, 
+                cmd.rpc_url, 
+                cmd.verbose,
+            ).await
+        },
+        Commands::SetNftBalance(cmd) => {
+            set_nft_balance(
+                cmd.token, 
+                cmd.holder, 
+                cmd.target_balance, 
+                cmd.rpc_url, 
+                cmd.verbose,
+            ).await
+        },
+        Commands::SetMultiTokenBalance(cmd) => {
+            set_multi_token_balance(
+                cmd.token, 
+                cmd.holder, 
+                cmd.id, 
+                cmd.target_balance, 
+                cmd.rpc_url, 
+                cmd.verbose,
+            ).await
+        },
     }
 }
 
@@ -39,11 +85,12 @@ async fn find_storage_slots(
     tokens: String,
     rpc_url: Option<String>,
     fork_rpc_url: Option<String>,
+    local_trace: bool,
+    poke_fallback: bool,
     unformatted_output: bool,
 ) -> Result<()> {
     let tokens = utils::parse_tokens_str(tokens)?;
 
-    // todo: use poor-mans-tracer instead of spinning up anvil
     let (rpc_url, _anvil) = 
         if let Some(fork_rpc_url) = fork_rpc_url {
             let anvil = utils::spawn_anvil(Some(&fork_rpc_url));
@@ -54,10 +101,19 @@ async fn find_storage_slots(
 
     // todo: consider rpc limit
     let provider = Arc::new(utils::http_provider_from_url(&rpc_url));
+    let capabilities = token_bss::Capabilities::probe(&provider).await;
     let mut task_set = tokens.into_iter().map(|token| {
         let provider = provider.clone();
         async move {
-            (token, token_bss::find_slot(&provider, token, None, None).await)
+            let finder = token_bss::SlotFinder::new(&provider, token)
+                .with_capabilities(capabilities)
+                .with_poke_fallback(poke_fallback);
+            let res = if local_trace {
+                finder.with_tracer(token_bss::RevmTracer).find().await
+            } else {
+                finder.find().await
+            }.map(|search| search.best);
+            (token, res)
         }
     }).collect::<JoinSet<_>>();
 
@@ -69,11 +125,62 @@ async fn find_storage_slots(
     Ok(())
 }
 
+fn find_bytecode_slot(
+    hex_file: String,
+    contract: Option<String>,
+    holder: Option<String>,
+    unformatted_output: bool,
+) -> Result<()> {
+    let code = utils::read_hex_file(&hex_file)?;
+    let contract = contract.map(|c| utils::parse_token_str(&c)).transpose()?.unwrap_or_default();
+    let holder = holder.map(|h| utils::parse_token_str(&h)).transpose()?;
+
+    match token_bss::find_bytecode_balance_slots(code, contract, holder) {
+        Ok(candidates) => candidates.into_iter().for_each(|locator| {
+            utils::format_find_slot_out(contract, Ok(locator), unformatted_output)
+        }),
+        Err(err) => utils::format_find_slot_out(contract, Err(err), unformatted_output),
+    }
+    Ok(())
+}
+
+async fn check_storage_layout(
+    token: String,
+    layout_file: String,
+    rpc_url: Option<String>,
+    local_trace: bool,
+) -> Result<()> {
+    let token = utils::parse_token_str(&token)?;
+    let layout = token_bss::StorageLayout::from_json(&std::fs::read_to_string(layout_file)?)?;
+    let rpc_url = rpc_url.unwrap_or(DEFAULT_RPC_URL.to_string());
+    let provider = utils::http_provider_from_url(&rpc_url);
+
+    let check = if local_trace {
+        token_bss::check_storage_layout(&provider, token, &layout, token_bss::RevmTracer).await
+    } else {
+        token_bss::check_storage_layout(&provider, token, &layout, token_bss::RpcTracer).await
+    };
+    match check {
+        Ok(check) => {
+            println!("Layout:");
+            utils::format_find_slot_out(token, Ok(check.layout.clone()), false);
+            println!("Trace:");
+            utils::format_find_slot_out(token, Ok(check.traced.clone()), false);
+            if let Some(warning) = check.warning() {
+                println!("Warning: {warning}");
+            }
+        },
+        Err(err) => utils::format_find_slot_out(token, Err(err), false),
+    }
+    Ok(())
+}
+
 async fn set_balance(
     token: String, 
     holder: String, 
     target_balance: f64,
     rpc_url: Option<String>,
+    sync_total_supply: bool,
     verbose: bool,
 ) -> Result<()> {
     let token = utils::parse_token_str(&token)?;
@@ -84,10 +191,92 @@ async fn set_balance(
     }
     let rpc_url = rpc_url.unwrap_or(DEFAULT_RPC_URL.to_string());
     let provider = utils::http_provider_from_url(&rpc_url);
-    let resulting_bal = balance_setter::set_balance(
+    let mut setter = token_bss::BalanceSetter::new(&provider, token);
+    if sync_total_supply {
+        setter = setter.with_total_supply_sync(None);
+    }
+    let write = setter.set_decimal(holder, target_balance).await?;
+    if verbose {
+        println!("New balance: {}", write.balance.value);
+        if !write.balance.is_exact() {
+            println!("Off target by: {}", write.balance.deviation());
+        }
+        if let Some(total_supply) = write.total_supply {
+            println!("New total supply: {}", total_supply.value);
+        }
+    }
+    Ok(())
+}
+
+async fn set_nft_owner(
+    token: String, 
+    id: String, 
+    owner: String,
+    rpc_url: Option<String>,
+    verbose: bool,
+) -> Result<()> {
+    let token = utils::parse_token_str(&token)?;
+    let id = utils::parse_uint_str(&id)?;
+    let owner = utils::parse_token_str(&owner)?;
+
+    if verbose {
+        println!("Setting owner of token {token:?} id {id} to {owner:?}");
+    }
+    let rpc_url = rpc_url.unwrap_or(DEFAULT_RPC_URL.to_string());
+    let provider = utils::http_provider_from_url(&rpc_url);
+    let resulting_owner = token_bss::set_owner(&provider, token, id, owner, None).await?;
+    if verbose {
+        println!("New owner: {resulting_owner:?}");
+    }
+    Ok(())
+}
+
+async fn set_nft_balance(
+    token: String, 
+    holder: String, 
+    target_balance: String,
+    rpc_url: Option<String>,
+    verbose: bool,
+) -> Result<()> {
+    let token = utils::parse_token_str(&token)?;
+    let holder = utils::parse_token_str(&holder)?;
+    let target_balance = utils::parse_uint_str(&target_balance)?;
+
+    if verbose {
+        println!("Setting NFT balance for token {token:?} and holder {holder:?} to {target_balance}");
+    }
+    let rpc_url = rpc_url.unwrap_or(DEFAULT_RPC_URL.to_string());
+    let provider = utils::http_provider_from_url(&rpc_url);
+    let resulting_bal = token_bss::set_nft_balance(&provider, token, holder, target_balance, None).await?;
+    if verbose {
+        println!("New balance: {}", resulting_bal);
+    }
+    Ok(())
+}
+
+async fn set_multi_token_balance(
+    token: String, 
+    holder: String, 
+    id: String,
+    target_balance: String,
+    rpc_url: Option<String>,
+    verbose: bool,
+) -> Result<()> {
+    let token = utils::parse_token_str(&token)?;
+    let holder = utils::parse_token_str(&holder)?;
+    let id = utils::parse_uint_str(&id)?;
+    let target_balance = utils::parse_uint_str(&target_balance)?;
+
+    if verbose {
+        println!("Setting balance for token {token:?} id {id} and holder {holder:?} to {target_balance}");
+    }
+    let rpc_url = rpc_url.unwrap_or(DEFAULT_RPC_URL.to_string());
+    let provider = utils::http_provider_from_url(&rpc_url);
+    let resulting_bal = token_bss::set_multi_token_balance(
         &provider,
         token, 
         holder, 
+        id,
         target_balance, 
         None
     ).await?;
@@ -96,4 +285,3 @@ async fn set_balance(
     }
     Ok(())
 }
-
@@ -2,8 +2,9 @@ use alloy::{
     providers::ReqwestProvider,
     network::Ethereum,
     node_bindings::{Anvil, AnvilInstance},
-    primitives::{Address, B256},
+    primitives::{Address, Bytes, U256},
 };
+use token_bss::{StorageLocator, Error};
 use eyre::Result;
 
 
@@ -21,6 +22,19 @@ pub fn parse_token_str(token_str: &str) -> Result<Address> {
     Ok(token)
 }
 
+// Decimal or 0x-prefixed hex
+pub fn parse_uint_str(uint_str: &str) -> Result<U256> {
+    let value = uint_str.trim().parse::<U256>()?;
+    Ok(value)
+}
+
+// Whitespace and an optional 0x prefix are ignored
+pub fn read_hex_file(path: &str) -> Result<Bytes> {
+    let contents = std::fs::read_to_string(path)?;
+    let code = contents.split_whitespace().collect::<String>().parse::<Bytes>()?;
+    Ok(code)
+}
+
 pub fn spawn_anvil(fork_url: Option<&str>) -> AnvilInstance {
     (match fork_url {
         Some(url) => Anvil::new().fork(url),
@@ -28,9 +42,9 @@ pub fn spawn_anvil(fork_url: Option<&str>) -> AnvilInstance {
     }).spawn()
 }
 
-pub fn format_find_slot_out(token: Address, res: Result<(Address, B256, f64, String)>, unformatted_output: bool) {
+pub fn format_find_slot_out(token: Address, res: token_bss::Result<StorageLocator>, unformatted_output: bool) {
     match res {
-        Result::Ok((contract, slot, update_ratio, lang)) => {
+        Result::Ok(StorageLocator { contract, slot, update_ratio, confidence, lang, strategy, family, proxy, .. }) => {
             if unformatted_output {
                 println!("{token:?},{contract:?},{slot:?},{update_ratio},{lang},");
             } else {
@@ -38,7 +52,15 @@ pub fn format_find_slot_out(token: Address, res: Result<(Address, B256, f64, Str
                 println!("Contract: {contract:?}");
                 println!("Slot: {slot:?}");
                 println!("Update ratio: {update_ratio}");
+                println!("Confidence: {confidence}");
                 println!("Language: {lang}");
+                println!("Strategy: {strategy}");
+                if let Some(family) = family {
+                    println!("Family: {family}");
+                }
+                if let Some(proxy) = proxy {
+                    println!("Proxy: {} -> {:?}", proxy.kind, proxy.implementation);
+                }
                 println!();
             }
         },
@@ -47,12 +69,35 @@ pub fn format_find_slot_out(token: Address, res: Result<(Address, B256, f64, Str
                 println!("{token:?},,,,,Error: {e:}");
             } else {
                 println!("Token: {token:?}");
-                println!("Error: {e:?}");
+                println!("Error: {e}");
+                if let Some(hint) = error_hint(&e) {
+                    println!("Hint: {hint}");
+                }
+                println!();
             }
         },
     }
 }
 
+fn error_hint(err: &Error) -> Option<&'static str> {
+    match err {
+        Error::TraceUnsupported => Some("RPC does not support debug_traceCall with memory, try --fork-rpc-url"),
+        Error::TraceCallFailed(_) => Some("balanceOf call failed for the token, check it is an ERC20 on this network"),
+        Error::NoSupportedStrategy => Some("RPC supports neither struct-log tracing nor access lists with state overrides, try --fork-rpc-url"),
+        Error::UnknownTokenFamily => Some("token code matches no known implementation, search with tracing instead"),
+        Error::NoLayoutMatch => Some("layout has no address => uint mapping labelled like balances"),
+        Error::NoPreimageMatch => Some("balanceOf did not read a mapping keyed by the holder"),
+        Error::VerificationFailed | Error::BalanceNotWritable => Some("overriding the traced slots did not change the balance"),
+        Error::UnresolvedIndirectKey => Some("balance is keyed by an id the holder does not have yet, use a holder with an existing balance"),
+        Error::RawKeyMismatch => Some("slot was found by poking storage for another holder, search again for this holder"),
+        Error::Timeout => Some("search took too long, the RPC may be rate limited"),
+        Error::TotalSupplyMismatch { .. } => Some("totalSupply does not report the adjusted counter, set the balance without syncing the supply"),
+        Error::ValueOverflow(_) => Some("target balance does not fit into the packed balance field"),
+        Error::ZeroRatioDenominator => Some("cached locator has a zero update ratio denominator, search the slot again"),
+        Error::Provider(_) | Error::Other(_) => None,
+    }
+}
+
 pub fn http_provider_from_url(url: &str) -> ReqwestProvider {
     ReqwestProvider::<Ethereum>::new_http(url.parse().unwrap())
 }
@@ -3,6 +3,7 @@ use eyre::Result;
 
 
 pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
+pub const CAPABILITIES_TTL_SECS: u64 = 3600;
 pub const DEFAULT_HOST: &str = "127.0.0.1";
 const DEFAULT_PORT: u32 = 3000;
 
@@ -12,6 +13,7 @@ pub struct Config {
     pub redis_config: Option<RedisConfig>,
     pub logging_enabled: bool,
     pub timeout_ms: u64,
+    pub poke_fallback: bool,
     pub anvil_config: Option<AnvilConfig>,
 }
 
@@ -117,6 +119,9 @@ impl Config {
         let timeout_ms = std::env::var("TIMEOUT_MS").ok()
             .and_then(|t_str| t_str.parse::<u64>().ok())
             .unwrap_or(DEFAULT_TIMEOUT_MS);
+        // Poked locations only hold for the traced holder
+        let poke_fallback = std::env::var("POKE_FALLBACK").ok()
+            .map(|s| s != "0").unwrap_or(true);
 
         Ok(Self {
             logging_enabled,
@@ -125,6 +130,7 @@ impl Config {
             redis_config,
             server_addr,
             timeout_ms,
+            poke_fallback,
         })
     }
 }
\ No newline at end of file
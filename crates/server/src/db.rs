@@ -33,7 +33,8 @@ impl RedisConnection {
     pub fn get_search_response(&mut self, address: &Address, chain_id: &Chain) -> Result<Option<SearchResponseWrapper>> {
         let key = make_key(address, chain_id);
         let val_str: Option<String> = self.0.get(&key)?;
-        let val = val_str.map(|val| serde_json::from_str(&val)).transpose()?;
+        // Entries stored under an older schema are treated as missing
+        let val = val_str.and_then(|val| serde_json::from_str(&val).ok());
         Ok(val)
     }
 
@@ -1,4 +1,4 @@
-use alloy::primitives::{Address, U256};
+use alloy::primitives::Address;
 use alloy::transports::Transport;
 use alloy::providers::Provider;
 use serde::{Serialize, Deserialize};
@@ -9,8 +9,9 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
 };
-use tracing::{info, error};
-use super::state::{Chain, AppState};
+use tracing::{info, warn, error};
+use token_bss::StorageLocator;
+use super::state::{Chain, AppState, AppProvider};
 
 
 #[derive(Debug, Serialize)]
@@ -77,11 +78,8 @@ impl From<SearchResponse> for SearchResponseWrapper {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SearchResponse {
     token: Address,
-    contract: Address,
-    slot: U256,
-    #[serde(rename = "updateRatio")]
-    update_ratio: f64,
-    lang: String,
+    #[serde(flatten)]
+    locator: StorageLocator,
 }
 
 #[derive(Debug)]
@@ -197,55 +195,61 @@ async fn _search_handler<P, T, H>(
     let token: Address = token_str.parse()
         .map_err(|_| AppError::UserError(UserError::InvalidToken))?;
 
-    if let Some(db_conn) = &app_state.db_connection {
-        let mut db_conn = db_conn.lock().unwrap();
-        let response = db_conn.get_search_response(&token, &chain)?;
-        if let Some(response) = response {
-            return match response {
-                SearchResponseWrapper::NotFound => Err(AppError::UserError(UserError::SlotNotFound)),
-                SearchResponseWrapper::Found(entry) => Ok((Json(Response::from(entry)), InfoSource::Database)),
-            };
-        }
-    }
-
     let provider = &app_state.providers
         .get(&chain)
         .ok_or(AppError::UserError(UserError::ProviderNotFound))?;
 
-    let trace_fn =
-        if provider.local_tracing {
-            let prov_clone = provider.provider.clone();
-            let trace_fn: token_bss::TraceFn = Box::new(move |a, b, c| {
-                // todo: make the caller provide the provider as an arg
-                poor_mans_tracer::geth_trace_sync(&prov_clone, &a, b, c)
-            });
-            Some(trace_fn)
-        } else {
-            None
-        };
-
-    let response = token_bss::find_slot(&provider.provider, token, None, trace_fn).await
-        .map_err(|err| {
-            if err.to_string().contains("No valid slots found") {
-                if let Some(db_conn) = &app_state.db_connection {
-                    let mut db_conn = db_conn.lock().unwrap();
-                    let response = SearchResponseWrapper::NotFound;
-                    db_conn.store_search_response(&token, &chain, &response).unwrap(); // todo dont unwrap!
-                }
-                AppError::UserError(UserError::SlotNotFound)
-            } else {
-                AppError::InternalError(err)
+    let cached = match &app_state.db_connection {
+        Some(db_conn) => db_conn.lock().unwrap().get_search_response(&token, &chain)?,
+        None => None,
+    };
+    match cached {
+        Some(SearchResponseWrapper::NotFound) => return Err(AppError::UserError(UserError::SlotNotFound)),
+        Some(SearchResponseWrapper::Found(entry)) if entry.locator.is_current_version() => {
+            // Proxies upgraded since the search may keep balances elsewhere.
+            // If the check itself fails, the cached entry is the best answer.
+            match token_bss::is_upgraded(&provider.provider, token, &entry.locator).await {
+                Ok(true) => (),
+                Ok(false) => return Ok((Json(Response::from(entry)), InfoSource::Database)),
+                Err(err) => {
+                    warn!("Upgrade check failed for {token} on {chain_str}: {err:#}");
+                    return Ok((Json(Response::from(entry)), InfoSource::Database))
+                },
             }
-        })?;
+        },
+        Some(SearchResponseWrapper::Found(_)) => (), // Outdated schema, search again
+        None => (),
+    }
+
+    let capabilities = provider.capabilities().await;
+    let poke_fallback = app_state.poke_fallback;
+    let mut search_res = search_slot(provider, token, capabilities, poke_fallback).await;
+    // The probe may be stale or have hit a transient error
+    if let Err(token_bss::Error::NoSupportedStrategy) = search_res {
+        let reprobed = provider.reprobe_capabilities().await;
+        if reprobed != capabilities {
+            search_res = search_slot(provider, token, reprobed, poke_fallback).await;
+        }
+    }
 
-    let response = SearchResponse {
-        token,
-        contract: response.0,
-        slot: response.1.into(),
-        update_ratio: response.2,
-        lang: response.3,
+    let locator = match search_res {
+        Ok(locator) => locator,
+        Err(token_bss::Error::NoPreimageMatch | token_bss::Error::VerificationFailed) => {
+            if let Some(db_conn) = &app_state.db_connection {
+                let mut db_conn = db_conn.lock().unwrap();
+                let response = SearchResponseWrapper::NotFound;
+                db_conn.store_search_response(&token, &chain, &response)?;
+            }
+            return Err(AppError::UserError(UserError::SlotNotFound))
+        },
+        // Only the fingerprint missed, another provider could still find it
+        Err(token_bss::Error::UnknownTokenFamily) => return Err(AppError::UserError(UserError::SlotNotFound)),
+        Err(token_bss::Error::Timeout) => return Err(AppError::UserError(UserError::Timeout)),
+        Err(err) => return Err(AppError::InternalError(err.into())),
     };
 
+    let response = SearchResponse { token, locator };
+
     if let Some(db_conn) = app_state.db_connection {
         let mut db_conn = db_conn.lock().unwrap();
         let response = response.clone().into();
@@ -253,4 +257,26 @@ async fn _search_handler<P, T, H>(
     }
 
     Ok((Json(response.into()), InfoSource::Provider))
-}
\ No newline at end of file
+}
+
+async fn search_slot<P, T, H>(
+    provider: &AppProvider<P, T, H>,
+    token: Address,
+    capabilities: token_bss::Capabilities,
+    poke_fallback: bool,
+) -> Result<StorageLocator, token_bss::Error> 
+    where P: Provider<T> + 'static, T: Transport + Clone, H: Sync + Send + Clone + 'static
+{
+    let finder = token_bss::SlotFinder::new(&provider.provider, token)
+        .with_capabilities(capabilities)
+        .with_poke_fallback(poke_fallback);
+    if provider.local_tracing {
+        let prov_clone = provider.provider.clone();
+        let tracer = token_bss::LocalTracer::new(move |a, b, c| {
+            poor_mans_tracer::geth_trace_sync(&prov_clone, &a, b, c)
+        });
+        finder.with_tracer(tracer).find().await
+    } else {
+        finder.find().await
+    }.map(|search| search.best)
+}
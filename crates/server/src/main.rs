@@ -25,6 +25,7 @@ async fn main() -> Result<()> {
         app_providers.build(), 
         redis_conn,
         configs.timeout_ms,
+        configs.poke_fallback,
     );
     server::run(&configs.server_addr, app_state).await?;
     Ok(())
@@ -4,13 +4,15 @@ use std::{
     sync::{Arc, Mutex},
     str::FromStr,
     hash::Hash,
+    time::{Duration, Instant},
 };
 use alloy::{
     transports::Transport,
     providers::Provider, 
 };
+use token_bss::Capabilities;
 use crate::{
-    config::DEFAULT_TIMEOUT_MS,
+    config::{DEFAULT_TIMEOUT_MS, CAPABILITIES_TTL_SECS},
     db::RedisConnection,
 };
 
@@ -22,6 +24,7 @@ pub struct AppState<P, T, H>
     pub providers: Arc<HashMap<Chain, AppProvider<P, T, H>>>,
     pub db_connection: Option<Arc<Mutex<RedisConnection>>>,
     pub timeout_ms: u64,
+    pub poke_fallback: bool,
 }
 
 impl<P, T, H> AppState<P, T, H> 
@@ -31,11 +34,13 @@ impl<P, T, H> AppState<P, T, H>
         providers: HashMap<Chain, AppProvider<P, T, H>>,
         db_connection: Option<RedisConnection>,
         timeout_ms: u64,
+        poke_fallback: bool,
     ) -> Self {
         Self {
             providers: Arc::new(providers),
             db_connection: db_connection.map(|conn| Arc::new(Mutex::new(conn))),
             timeout_ms,
+            poke_fallback,
         }
     }
 }
@@ -59,6 +64,7 @@ impl<P, T, H> AppProviders<P, T, H>
     ) {
         self.0.insert(chain, AppProvider { 
             provider: Arc::new(provider),
+            capabilities: Arc::new(Mutex::new(None)),
             _handler: handler,
             local_tracing,
             _phantom_transport: std::marker::PhantomData,
@@ -77,6 +83,7 @@ impl<P, T, H> From<AppProviders<P, T, H>> for AppState<P, T, H>
         Self {
             providers: Arc::new(providers.build()),
             timeout_ms: DEFAULT_TIMEOUT_MS,
+            poke_fallback: true,
             db_connection: None
         }
     }
@@ -87,10 +94,36 @@ pub struct AppProvider<P, T, H>
 {
     pub provider: Arc<P>,
     pub local_tracing: bool,
+    capabilities: Arc<Mutex<Option<(Capabilities, Instant)>>>, // Probed on first use
     _handler: Option<H>, // Handler for cases like Anvil
     _phantom_transport: std::marker::PhantomData<T>,
 }
 
+impl<P, T, H> AppProvider<P, T, H> 
+    where P: Provider<T>, T: Transport + Clone, H: Sync + Send + Clone + 'static
+{
+    /// Capabilities of the provider, probed on first use and again once stale
+    pub async fn capabilities(&self) -> Capabilities {
+        let ttl = Duration::from_secs(CAPABILITIES_TTL_SECS);
+        let cached = *self.capabilities.lock().unwrap();
+        if let Some((capabilities, probed_at)) = cached {
+            if probed_at.elapsed() < ttl {
+                return capabilities
+            }
+        }
+        let capabilities = Capabilities::probe(&*self.provider).await;
+        *self.capabilities.lock().unwrap() = Some((capabilities, Instant::now()));
+        capabilities
+    }
+
+    /// Probe the capabilities again, eg. after the endpoint stopped serving
+    /// a method or a probe failed on a transient error
+    pub async fn reprobe_capabilities(&self) -> Capabilities {
+        self.capabilities.lock().unwrap().take();
+        self.capabilities().await
+    }
+}
+
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Chain {
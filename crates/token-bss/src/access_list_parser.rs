@@ -0,0 +1,70 @@
+use alloy::rpc::types::eth::AccessList;
+use std::collections::HashMap;
+use super::{lang::EvmLanguage, locator::StorageLocator};
+use crate::common::*;
+
+
+// Base slots tried for each touched storage key, access lists carry no preimages
+const MAX_SLOT_INDEX: u64 = 256;
+
+/// Matches the storage keys of an access list against mapping locations 
+/// derived from a range of base slots
+pub struct AccessListParser;
+
+impl AccessListParser {
+
+    /// Storage candidates whose key is a mapping location for `keys` 
+    /// (outermost first) at a base slot below `MAX_SLOT_INDEX`
+    pub fn parse(access_list: &AccessList, keys: &[B256]) -> Result<Vec<StorageLocator>> {
+        let locations = Self::expected_locations(keys);
+        let candidates: Vec<_> = access_list.0.iter()
+            .flat_map(|item| item.storage_keys.iter().map(move |key| (item.address, key)))
+            .filter_map(|(contract, key)| {
+                let (slot, lang) = locations.get(key)?;
+                Some(StorageLocator::new(contract, *slot, *lang))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Err(Error::NoPreimageMatch);
+        }
+        Ok(candidates)
+    }
+
+    fn expected_locations(keys: &[B256]) -> HashMap<B256, (B256, EvmLanguage)> {
+        (0..MAX_SLOT_INDEX)
+            .map(|idx| B256::from(U256::from(idx)))
+            .flat_map(|slot| {
+                [EvmLanguage::Solidity, EvmLanguage::Vyper].into_iter()
+                    .map(move |lang| (lang.nested_mapping_loc(slot, keys), (slot, lang)))
+            })
+            .collect()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::rpc::types::eth::AccessListItem;
+    use super::*;
+
+    #[test]
+    fn test_access_list_match() {
+        let token = Address::with_last_byte(0xaa);
+        let storage = Address::with_last_byte(0xbb);
+        let holder = Address::with_last_byte(1);
+        let slot = B256::from(U256::from(51));
+        let access_list = AccessList(vec![
+            AccessListItem { address: token, storage_keys: vec![B256::from(U256::from(3))] },
+            AccessListItem { 
+                address: storage, 
+                storage_keys: vec![EvmLanguage::Vyper.mapping_loc(slot, holder)],
+            },
+        ]);
+        let result = AccessListParser::parse(&access_list, &[holder.into_word()]).unwrap();
+        assert_eq!(result, vec![StorageLocator::new(storage, slot, EvmLanguage::Vyper)]);
+
+        let result = AccessListParser::parse(&access_list, &[Address::with_last_byte(2).into_word()]);
+        assert!(matches!(result, Err(Error::NoPreimageMatch)));
+    }
+
+}
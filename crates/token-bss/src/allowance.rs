@@ -0,0 +1,82 @@
+use super::{
+    slot_finder::SlotFinder,
+    locator::StorageLocator,
+    getter::Getter,
+    setter,
+    tracer::{Tracer, RpcTracer},
+};
+use crate::common::*;
+
+
+/// Locate `allowance[owner][spender]` of `token`
+pub async fn find_allowance_slot<P, T>(
+    provider: &P,
+    token: Address,
+    owner: Option<Address>,
+    spender: Option<Address>,
+    tracer: impl Tracer,
+) -> Result<StorageLocator>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let owner = owner.unwrap_or_else(crate::default_holder);
+    let spender = spender.unwrap_or_else(default_spender);
+    SlotFinder::new(provider, token)
+        .with_holder(owner)
+        .with_getter(Getter::allowance(owner, spender))
+        .with_tracer(tracer)
+        .find().await
+        .map(|search| search.best)
+}
+
+/// Write `amount` as the allowance of `spender` over the tokens of `owner`
+/// directly into storage, returning the allowance reported by the token
+pub async fn set_allowance<P, T>(
+    provider: &P,
+    token: Address,
+    owner: Address,
+    spender: Address,
+    amount: U256,
+    locator: Option<StorageLocator>,
+) -> Result<U256>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let locator = match locator {
+        Some(locator) => locator,
+        None => find_allowance_slot(provider, token, Some(owner), Some(spender), RpcTracer).await?,
+    };
+    let getter = Getter::allowance(owner, spender);
+    setter::set_getter_value(provider, token, &getter, amount, &locator).await
+}
+
+// Distinct from the default owner so the two keys can't be confused
+fn default_spender() -> Address {
+    Address::from_word(B256::from(U256::from(2)))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{node_bindings::Anvil, providers::ReqwestProvider};
+    use crate::lang::EvmLanguage;
+    use crate::test_utils::rpc_endpoint;
+    use super::*;
+    use eyre::Result;
+
+    #[tokio::test]
+    async fn test_allowance_eth_usdc() -> Result<()> {
+        let anvil = Anvil::new().fork(rpc_endpoint()?).spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let token: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse()?;
+        let owner: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse()?;
+        let spender: Address = "0x3fC91A3afd70395Cd496C647d5a6CC9D4B2b7FAD".parse()?;
+
+        let locator = find_allowance_slot(&provider, token, Some(owner), Some(spender), RpcTracer).await?;
+        assert_eq!(locator.slot, B256::from(U256::from(10)));
+        assert_eq!(locator.lang, EvmLanguage::Solidity);
+
+        let amount = U256::from(123_456_789);
+        let allowance = set_allowance(&provider, token, owner, spender, amount, Some(locator)).await?;
+        assert_eq!(allowance, amount);
+        Ok(())
+    }
+
+}
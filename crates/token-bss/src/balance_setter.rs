@@ -0,0 +1,287 @@
+use std::marker::PhantomData;
+use super::{
+    locator::{StorageLocator, StorageWrite},
+    getter::Getter,
+    setter::{self, ExactWrite},
+    tracer::RpcTracer,
+    writer::{StorageWriter, AnvilStorageWriter},
+    ops::token,
+};
+use crate::common::*;
+
+
+/// Outcome of a balance write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceWrite {
+    pub balance: ExactWrite,
+    /// `totalSupply` write, when the supply is kept in sync and had to change
+    pub total_supply: Option<ExactWrite>,
+}
+
+/// Builder for setting ERC20 balances on a dev node or a virtual testnet.
+/// The balance slot is searched for unless a locator is given, and storage
+/// is written with `anvil_setStorageAt` unless another writer is chosen.
+pub struct BalanceSetter<'a, P, T, W = AnvilStorageWriter> {
+    provider: &'a P,
+    token: Address,
+    locator: Option<StorageLocator>,
+    sync_total_supply: bool,
+    supply_locator: Option<StorageLocator>,
+    writer: W,
+    _phantom_transport: PhantomData<T>,
+}
+
+impl<'a, P, T> BalanceSetter<'a, P, T>
+    where P: Provider<T>, T: Transport + Clone
+{
+    pub fn new(provider: &'a P, token: Address) -> Self {
+        Self {
+            provider,
+            token,
+            locator: None,
+            sync_total_supply: false,
+            supply_locator: None,
+            writer: AnvilStorageWriter,
+            _phantom_transport: PhantomData,
+        }
+    }
+}
+
+impl<'a, P, T, W> BalanceSetter<'a, P, T, W>
+    where P: Provider<T>, T: Transport + Clone, W: StorageWriter
+{
+
+    /// Reuse a locator found earlier (eg. cached) instead of searching
+    pub fn with_locator(mut self, locator: StorageLocator) -> Self {
+        self.locator = Some(locator);
+        self
+    }
+
+    /// Adjust `totalSupply` by the same delta as the balance, so it stays
+    /// the sum of balances. Its slot is searched for by poking the storage
+    /// read by `totalSupply()`, unless a locator is given.
+    pub fn with_total_supply_sync(mut self, supply_locator: Option<StorageLocator>) -> Self {
+        self.sync_total_supply = true;
+        self.supply_locator = supply_locator;
+        self
+    }
+
+    pub fn with_writer<W2: StorageWriter>(self, writer: W2) -> BalanceSetter<'a, P, T, W2> {
+        BalanceSetter {
+            provider: self.provider,
+            token: self.token,
+            locator: self.locator,
+            sync_total_supply: self.sync_total_supply,
+            supply_locator: self.supply_locator,
+            writer,
+            _phantom_transport: PhantomData,
+        }
+    }
+
+    /// Set the balance of `holder` so that `balanceOf` reports `target`
+    /// (raw, fixed point), solving for the stored value of share-based tokens.
+    /// If the supply can't be kept in sync, the balance is left as it was.
+    pub async fn set(&self, holder: Address, target: U256) -> Result<BalanceWrite> {
+        let locator = match &self.locator {
+            Some(locator) => locator.clone(),
+            None => crate::find_slot(self.provider, self.token, Some(holder), RpcTracer).await?,
+        };
+        let getter = Getter::balance_of(holder);
+        if !self.sync_total_supply {
+            let balance = self.write_exact(&getter, target, &locator).await?;
+            return Ok(BalanceWrite { balance, total_supply: None });
+        }
+        let supply_locator = self.supply_locator().await?;
+        let previous = self.call(&getter).await?;
+        let restore = setter::current_word(self.provider, &getter, &locator).await?;
+        let balance = self.write_exact(&getter, target, &locator).await?;
+        match self.sync_total_supply(&supply_locator, previous, balance.value).await {
+            Ok(total_supply) => Ok(BalanceWrite { balance, total_supply }),
+            Err(err) => {
+                self.restore(restore).await?;
+                Err(err)
+            },
+        }
+    }
+
+    /// Same as [`BalanceSetter::set`] with `amount` in whole tokens,
+    /// scaled by the token's decimals
+    pub async fn set_decimal(&self, holder: Address, amount: f64) -> Result<BalanceWrite> {
+        let target = self.fixed_amount(amount).await?;
+        self.set(holder, target).await
+    }
+
+    // A supply kept as a plain counter (update ratio of one, as in Solidity,
+    // Vyper and Solady tokens) has to report the value it stores, or the 
+    // adjustment won't carry over. Checked before any balance is written.
+    async fn supply_locator(&self) -> Result<StorageLocator> {
+        let supply_locator = match &self.supply_locator {
+            Some(supply_locator) => supply_locator.clone(),
+            None => crate::find_total_supply_slot(self.provider, self.token, RpcTracer).await?,
+        };
+        if supply_locator.update_ratio.is_one() {
+            let getter = Getter::total_supply();
+            let word = setter::current_word(self.provider, &getter, &supply_locator).await?;
+            let stored = supply_locator.unpack(word.value);
+            let supply = self.call(&getter).await?;
+            if stored != supply {
+                return Err(Error::TotalSupplyMismatch { expected: stored, actual: supply });
+            }
+        }
+        Ok(supply_locator)
+    }
+
+    // A counter has to report the adjusted value exactly, a supply derived 
+    // from shares is solved for like a balance, within rounding
+    async fn sync_total_supply(
+        &self,
+        supply_locator: &StorageLocator,
+        previous: U256,
+        balance: U256,
+    ) -> Result<Option<ExactWrite>> {
+        let getter = Getter::total_supply();
+        let supply = self.call(&getter).await?;
+        let target = match balance >= previous {
+            true => supply.saturating_add(balance - previous),
+            false => supply.saturating_sub(previous - balance),
+        };
+        if target == supply {
+            return Ok(None);
+        }
+        let restore = setter::current_word(self.provider, &getter, supply_locator).await?;
+        let write = self.write_exact(&getter, target, supply_locator).await?;
+        if supply_locator.update_ratio.is_one() && !write.is_exact() {
+            self.restore(restore).await?;
+            return Err(Error::TotalSupplyMismatch { expected: target, actual: write.value });
+        }
+        Ok(Some(write))
+    }
+
+    async fn restore(&self, write: StorageWrite) -> Result<()> {
+        self.writer.set_storage_at(self.provider, write.contract, write.key, write.value).await
+    }
+
+    async fn write_exact(&self, getter: &Getter, target: U256, locator: &StorageLocator) -> Result<ExactWrite> {
+        // Nodes without state-override calls (eg. Hardhat) can still set
+        // values of tokens that report the stored value as is
+        let stored = match locator.update_ratio.is_one() {
+            true => target,
+            false => setter::solve_stored_value(self.provider, self.token, getter, target, locator).await?,
+        };
+        let value = setter::write_getter_value(
+            self.provider,
+            &self.writer,
+            self.token,
+            getter,
+            stored,
+            locator,
+        ).await?;
+        Ok(ExactWrite { target, stored, value })
+    }
+
+    async fn call(&self, getter: &Getter) -> Result<U256> {
+        let call_request = token::call_req(crate::default_holder(), self.token, getter.input());
+        token::call_request(self.provider, &call_request, BlockNumberOrTag::Latest).await
+    }
+
+    async fn fixed_amount(&self, amount: f64) -> Result<U256> {
+        let decimals = token::decimals(self.provider, self.token, BlockNumberOrTag::Latest).await?;
+        let amount = alloy_utils::parse_units(&amount.to_string(), decimals)
+            .map_err(|err| eyre::eyre!("Invalid amount {amount}: {err}"))?;
+        Ok(amount.into())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{node_bindings::Anvil, providers::ReqwestProvider};
+    use crate::lang::EvmLanguage;
+    use crate::test_utils::arb_rpc_endpoint;
+    use super::*;
+    use eyre::Result;
+
+    #[tokio::test]
+    async fn test_fixed_amount() -> Result<()> {
+        let provider = ReqwestProvider::new_http(arb_rpc_endpoint().parse()?);
+        let token: Address = "0x912CE59144191C1204E64559FE8253a0e49E6548".parse()?;
+        let amount = 23.434;
+        let expected: U256 = alloy_utils::parse_ether(&amount.to_string())?.into();
+
+        let fixed = BalanceSetter::new(&provider, token).fixed_amount(amount).await?;
+
+        assert_eq!(fixed, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_balance_with_locator() -> Result<()> {
+        let anvil = Anvil::new().fork(arb_rpc_endpoint()).spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let token: Address = "0xfa7f8980b0f1e64a2062791cc3b0871572f1f7f0".parse()?;
+        let holder: Address = "0x1f9090aaE28b8a3dCeaDf281B0F12828e676c326".parse()?;
+        let locator = StorageLocator::new(token, B256::from(U256::from(0x33)), EvmLanguage::Solidity);
+
+        let write = BalanceSetter::new(&provider, token)
+            .with_locator(locator)
+            .set(holder, U256::from(100))
+            .await?;
+
+        assert_eq!(write.balance.value, U256::from(100));
+        assert!(write.balance.is_exact());
+        assert_eq!(write.total_supply, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_off_counter_supply_is_rejected() -> Result<()> {
+        let anvil = Anvil::new().spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let token = Address::with_last_byte(0xaa);
+        // totalSupply() returning `sload(0) + 1`, balanceOf(address) `balances[holder]` at slot 1
+        let code = Bytes::from(hex::decode(
+            "60003560e01c6318160ddd14602857600435600052600160205260406000205460005260206000f3\
+            5b60016000540160005260206000f3"
+        )?);
+        provider.client().request::<_, ()>("anvil_setCode", (token, code)).await?;
+        let locator = StorageLocator::new(token, B256::from(U256::from(1)), EvmLanguage::Solidity);
+        let supply_locator = StorageLocator::raw(token, B256::ZERO, &[]);
+
+        let holder = Address::with_last_byte(0xbb);
+        let setter = BalanceSetter::new(&provider, token)
+            .with_locator(locator)
+            .with_total_supply_sync(Some(supply_locator));
+
+        let result = setter.set(holder, U256::from(1_000)).await;
+
+        assert!(matches!(
+            result,
+            Err(Error::TotalSupplyMismatch { expected, actual })
+                if expected == U256::ZERO && actual == U256::from(1)
+        ));
+        assert_eq!(setter.call(&Getter::balance_of(holder)).await?, U256::ZERO);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_total_supply_follows_balance() -> Result<()> {
+        let anvil = Anvil::new().fork(arb_rpc_endpoint()).spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let token: Address = "0xfa7f8980b0f1e64a2062791cc3b0871572f1f7f0".parse()?;
+        let holder: Address = "0x1f9090aaE28b8a3dCeaDf281B0F12828e676c326".parse()?;
+        let locator = StorageLocator::new(token, B256::from(U256::from(0x33)), EvmLanguage::Solidity);
+        let setter = BalanceSetter::new(&provider, token).with_locator(locator);
+        let supply = setter.call(&Getter::total_supply()).await?;
+        let balance = setter.call(&Getter::balance_of(holder)).await?;
+
+        let write = setter
+            .with_total_supply_sync(None)
+            .set(holder, balance + U256::from(1_000))
+            .await?;
+
+        let supply_write = write.total_supply.expect("supply should change");
+        assert_eq!(supply_write.value, supply + U256::from(1_000));
+        Ok(())
+    }
+
+}
@@ -0,0 +1,111 @@
+use super::{
+    getter::Getter,
+    locator::StorageLocator,
+    symbolic,
+    trace_parser::TraceParser,
+};
+use crate::common::*;
+
+
+/// Locate the storage behind `getter` from the runtime `code` alone, without
+/// any RPC. The getter is executed symbolically, with its arguments (eg. the 
+/// holder) and all storage as symbolic input, and `contract` holding `code`.
+/// Branches on symbolic values (eg. a pause flag or a holder check) are 
+/// explored both ways, and the keccak and SLOAD data flow that depends on 
+/// the input is matched against the getter keys, as for traced calls.
+///
+/// External calls are not followed, so locations behind proxies (the code
+/// only forwards the call) can't be found. Exploration is bounded, so getters 
+/// looping over storage may be cut short. Candidates can't be verified offline,
+/// so all of them are returned, direct mapping locations first.
+pub fn find_bytecode_slots(
+    code: Bytes,
+    contract: Address,
+    getter: Getter,
+) -> Result<Vec<StorageLocator>> {
+    let events = symbolic::explore(&code, contract, bytecode_caller(), &getter.input())?;
+    let mut candidates = TraceParser::parse_events(events, &getter.keys)?;
+    candidates.sort_by_key(|locator| (locator.indirect.is_some(), locator.slot_offset, locator.slot));
+    for locator in candidates.iter_mut() {
+        locator.key_order = getter.key_order();
+    }
+    Ok(candidates)
+}
+
+/// Same as [`find_bytecode_slots`] for `balanceOf(holder)`. The holder only 
+/// instantiates the symbolic input keys are matched with; the default one is 
+/// high enough for unhashed (`uint160(holder)`) locations to be detected.
+pub fn find_bytecode_balance_slots(
+    code: Bytes,
+    contract: Address,
+    holder: Option<Address>,
+) -> Result<Vec<StorageLocator>> {
+    let holder = holder.unwrap_or_else(default_bytecode_holder);
+    find_bytecode_slots(code, contract, Getter::balance_of(holder))
+}
+
+fn bytecode_caller() -> Address {
+    Address::repeat_byte(0xca)
+}
+
+fn default_bytecode_holder() -> Address {
+    Address::repeat_byte(0xb0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lang::EvmLanguage, locator::IndirectKey};
+    use super::*;
+
+    // Hand-assembled balanceOf(address) returning `balances[holder]` at slot 0:
+    // PUSH1 0x04 CALLDATALOAD PUSH1 0x00 MSTORE PUSH1 0x00 PUSH1 0x20 MSTORE
+    // PUSH1 0x40 PUSH1 0x00 SHA3 SLOAD PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN
+    const SOLIDITY_BALANCE_OF: &str = "600435600052600060205260406000205460005260206000f3";
+
+    // Same getter with the holder stored unhashed at `uint160(holder)`:
+    // PUSH1 0x04 CALLDATALOAD SLOAD PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN
+    const UNHASHED_BALANCE_OF: &str = "6004355460005260206000f3";
+
+    #[test]
+    fn test_solidity_mapping_from_bytecode() {
+        let code = Bytes::from(hex::decode(SOLIDITY_BALANCE_OF).unwrap());
+        let contract = Address::with_last_byte(0xaa);
+        let result = find_bytecode_balance_slots(code, contract, None).unwrap();
+        assert_eq!(result, vec![StorageLocator::new(contract, B256::ZERO, EvmLanguage::Solidity)]);
+    }
+
+    #[test]
+    fn test_unhashed_from_bytecode() {
+        let code = Bytes::from(hex::decode(UNHASHED_BALANCE_OF).unwrap());
+        let contract = Address::with_last_byte(0xaa);
+        let result = find_bytecode_balance_slots(code, contract, None).unwrap();
+        assert_eq!(result, vec![StorageLocator::new(contract, B256::ZERO, EvmLanguage::Unhashed)]);
+    }
+
+    #[test]
+    fn test_read_past_storage_check() {
+        // Reverts unless the flag at slot 5 is set, which it never is in empty storage:
+        // PUSH1 0x05 SLOAD PUSH1 0x0a JUMPI PUSH1 0x00 DUP1 REVERT JUMPDEST, then SOLIDITY_BALANCE_OF
+        let code = format!("600554600a57600080fd5b{SOLIDITY_BALANCE_OF}");
+        let code = Bytes::from(hex::decode(code).unwrap());
+        let contract = Address::with_last_byte(0xaa);
+        let result = find_bytecode_balance_slots(code, contract, None).unwrap();
+        assert_eq!(result, vec![StorageLocator::new(contract, B256::ZERO, EvmLanguage::Solidity)]);
+    }
+
+    #[test]
+    fn test_id_keyed_from_bytecode() {
+        // `balances[ids[holder]]`, with `ids` at slot 1 and `balances` at slot 0:
+        // PUSH1 0x04 CALLDATALOAD PUSH1 0x00 MSTORE PUSH1 0x01 PUSH1 0x20 MSTORE
+        // PUSH1 0x40 PUSH1 0x00 SHA3 SLOAD PUSH1 0x00 MSTORE PUSH1 0x00 PUSH1 0x20 MSTORE
+        // PUSH1 0x40 PUSH1 0x00 SHA3 SLOAD PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN
+        let code = "6004356000526001602052604060002054600052600060205260406000205460005260206000f3";
+        let code = Bytes::from(hex::decode(code).unwrap());
+        let contract = Address::with_last_byte(0xaa);
+        let result = find_bytecode_balance_slots(code, contract, None).unwrap();
+        let mut expected = StorageLocator::new(contract, B256::ZERO, EvmLanguage::Solidity);
+        expected.indirect = Some(IndirectKey { slot: B256::from(U256::from(1)), lang: EvmLanguage::Solidity });
+        assert!(result.contains(&expected));
+    }
+
+}
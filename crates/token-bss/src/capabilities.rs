@@ -0,0 +1,85 @@
+use alloy::rpc::types::eth::state::{AccountOverride, StateOverride};
+use serde::{Serialize, Deserialize};
+use super::{ops::{access_list, token, trace}, tracer::RpcTracer, utils};
+use crate::common::*;
+
+
+// PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+const RETURN_42_CODE: [u8; 10] = [0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+// PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 KECCAK256 STOP
+const HASH_42_CODE: [u8; 11] = [0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0x20, 0x00];
+
+/// RPC features the discovery strategies and setters rely on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// `debug_traceCall` with struct logs and memory
+    pub struct_log_trace: bool,
+    /// State override set for `eth_call`, needed to verify any candidate
+    pub state_overrides: bool,
+    /// `eth_createAccessList`
+    pub access_list: bool,
+    /// `anvil_*` methods, needed to write storage
+    pub anvil: bool,
+}
+
+impl Capabilities {
+
+    /// Probe `provider` with a cheap request per feature
+    pub async fn probe<P, T>(provider: &P) -> Self 
+        where P: Provider<T>, T: Transport + Clone
+    {
+        let probe_req = token::call_req(crate::default_holder(), probe_address(), Bytes::new());
+        let (struct_log_trace, state_overrides, access_list, anvil) = tokio::join!(
+            supports_struct_log_trace(provider, &probe_req),
+            supports_state_overrides(provider, &probe_req),
+            access_list::create_access_list(provider, &probe_req, BlockNumberOrTag::Latest),
+            provider.client().request::<_, bool>("anvil_getAutomine", ()),
+        );
+        Self {
+            struct_log_trace,
+            state_overrides,
+            access_list: access_list.is_ok(),
+            anvil: anvil.is_ok(),
+        }
+    }
+
+}
+
+// Preimages are read from the memory of KECCAK256 steps, so a trace without 
+// memory is as good as none
+async fn supports_struct_log_trace<P, T>(provider: &P, probe_req: &TransactionRequest) -> bool 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let state_override = probe_code_override(&HASH_42_CODE);
+    trace::default_trace_call(provider, &RpcTracer, probe_req.clone(), None, state_override).await
+        .map(|frame| frame.struct_logs.iter()
+            .any(|log| log.memory.as_ref().is_some_and(|memory| !memory.is_empty()))
+        )
+        .unwrap_or(false)
+}
+
+async fn supports_state_overrides<P, T>(provider: &P, probe_req: &TransactionRequest) -> bool 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let state_override = probe_code_override(&RETURN_42_CODE);
+    provider.call(probe_req)
+        .overrides(&state_override)
+        .block(BlockNumberOrTag::Latest.into())
+        .await
+        .map(|output| utils::bytes_to_u256(output) == U256::from(42))
+        .unwrap_or(false)
+}
+
+fn probe_code_override(code: &'static [u8]) -> StateOverride {
+    let account_override = AccountOverride {
+        code: Some(Bytes::from_static(code)),
+        ..AccountOverride::default()
+    };
+    [(probe_address(), account_override)].into_iter().collect()
+}
+
+// Arbitrary address without code on any network
+fn probe_address() -> Address {
+    Address::from_word(B256::from(U256::from(0x7b55)))
+}
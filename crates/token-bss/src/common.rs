@@ -1,6 +1,3 @@
-use alloy::rpc::types::eth::Header;
-use alloy::rpc::types::trace::geth::{GethTrace, GethDebugTracingCallOptions};
-
 pub use alloy::{
     primitives::{
         Address, B256, U512, U256, U160, U128, Bytes, FixedBytes,
@@ -12,10 +9,4 @@ pub use alloy::{
     transports::Transport,
 };
 
-pub use eyre::Result;
-
-pub type TraceFn = Box<dyn Fn(
-    TransactionRequest,
-    Header, 
-    GethDebugTracingCallOptions,
-) -> Result<GethTrace, eyre::Report> + Send>;
\ No newline at end of file
+pub use crate::error::{Error, Result};
@@ -0,0 +1,67 @@
+use alloy::{primitives::U256, transports::TransportError};
+
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors of the slot discovery pipeline
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Provider can't produce default struct-log traces with memory
+    #[error("Struct-log tracing is not supported by the provider")]
+    TraceUnsupported,
+    /// Traced call reverted or the tracer itself failed
+    #[error("traceCall failed: {0}")]
+    TraceCallFailed(String),
+    /// Provider lacks state overrides, or the features every strategy needs
+    #[error("Provider supports none of the discovery strategies")]
+    NoSupportedStrategy,
+    /// Token code matches none of the known implementations
+    #[error("Token implementation not recognised")]
+    UnknownTokenFamily,
+    /// Storage layout has no `address => uint` mapping labelled like balances
+    #[error("No balance mapping found in the storage layout")]
+    NoLayoutMatch,
+    /// No storage read in the trace was keyed by a hash of the holder
+    #[error("No keccak preimage matched the holder")]
+    NoPreimageMatch,
+    /// Candidate slots were found, but overriding none of them changed the balance
+    #[error("No valid slots found")]
+    VerificationFailed,
+    /// Overriding the slot did not change the reported balance
+    #[error("Balance not updated")]
+    BalanceNotWritable,
+    /// Value is wider than the balance field it is written to
+    #[error("Value does not fit into {0} bits")]
+    ValueOverflow(u16),
+    /// Value is keyed by an id the holder has not been assigned yet
+    #[error("Holder has no id to key the value with")]
+    UnresolvedIndirectKey,
+    /// Raw storage key was found for other keys (eg. another holder)
+    #[error("Location is only valid for the keys it was found for")]
+    RawKeyMismatch,
+    /// Update ratio measured (or deserialized) without a value written
+    #[error("Update ratio has a zero denominator")]
+    ZeroRatioDenominator,
+    /// `totalSupply` slot doesn't report the value stored or written to it
+    #[error("totalSupply reports {actual} instead of {expected}")]
+    TotalSupplyMismatch { expected: U256, actual: U256 },
+    #[error("Slot search timed out")]
+    Timeout,
+    #[error("Provider error: {0}")]
+    Provider(#[from] TransportError),
+    #[error(transparent)]
+    Other(#[from] eyre::Report),
+}
+
+impl Error {
+
+    /// Whether the search ran cleanly but found no slot, as opposed to failing
+    /// (eg. on a transport error) before it could tell
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self, 
+            Error::NoPreimageMatch | Error::VerificationFailed | Error::UnknownTokenFamily
+        )
+    }
+
+}
@@ -0,0 +1,168 @@
+use alloy::{primitives::b256, rpc::types::eth::state::StateOverride};
+use serde::{Serialize, Deserialize};
+use super::{lang::EvmLanguage, locator::StorageLocator, ops::state, proxy::EIP1967_IMPLEMENTATION_SLOT};
+use crate::common::*;
+
+
+// ERC-7201 location of `ERC20Storage` in OpenZeppelin 5 upgradeable contracts
+const OZ_ERC20_STORAGE_LOCATION: B256 = b256!("52c63247e1f47db19d5ce0460030c497f067ca4cebf71ba98eeadabe20bace00");
+// PUSH32 <OZ_ERC20_STORAGE_LOCATION>
+const OZ_NAMESPACED_MARKER: [u8; 33] = [
+    0x7f, 0x52, 0xc6, 0x32, 0x47, 0xe1, 0xf4, 0x7d, 0xb1, 0x9d, 0x5c, 0xe0, 0x46, 0x00, 0x30, 0xc4, 0x97,
+    0xf0, 0x67, 0xca, 0x4c, 0xeb, 0xf7, 0x1b, 0xa9, 0x8e, 0xea, 0xda, 0xbe, 0x20, 0xba, 0xce, 0x00,
+];
+// PUSH4 `_BALANCE_SLOT_SEED`
+const SOLADY_MARKER: [u8; 5] = [0x63, 0x87, 0xa2, 0x11, 0xa2];
+const SOLADY_BALANCE_SEED: u64 = 0x87a211a2;
+// PUSH4 `ERC20InsufficientBalance.selector`
+const OZ5_MARKER: [u8; 5] = [0x63, 0xe4, 0x50, 0xd3, 0x8c];
+const OZ4_MARKER: &[u8] = b"ERC20: transfer amount exceeds balance";
+const SOLMATE_MARKER: &[u8] = b"PERMIT_DEADLINE_EXPIRED";
+// CBOR metadata key appended by the Vyper compiler
+const VYPER_MARKER: &[u8] = b"\xa1\x65vyper";
+
+/// Known token implementation, recognised from its runtime code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TokenFamily {
+    /// OpenZeppelin 4 `ERC20`, or `ERC20Upgradeable` after its gaps
+    OpenZeppelin4,
+    /// OpenZeppelin 5 `ERC20`
+    OpenZeppelin5,
+    /// OpenZeppelin 5 `ERC20Upgradeable`, with ERC-7201 namespaced storage
+    OpenZeppelin5Upgradeable,
+    Solmate,
+    Solady,
+    /// Vyper templates, with the balance mapping in one of the first few slots
+    Vyper,
+}
+
+impl TokenFamily {
+
+    /// Families in the order they are matched, most specific marker first
+    const MARKERS: [(TokenFamily, &'static [u8]); 6] = [
+        (TokenFamily::Solady, &SOLADY_MARKER),
+        (TokenFamily::OpenZeppelin5Upgradeable, &OZ_NAMESPACED_MARKER),
+        (TokenFamily::OpenZeppelin5, &OZ5_MARKER),
+        (TokenFamily::Solmate, SOLMATE_MARKER),
+        (TokenFamily::OpenZeppelin4, OZ4_MARKER),
+        (TokenFamily::Vyper, VYPER_MARKER),
+    ];
+
+    /// Classify runtime `code`
+    pub fn from_code(code: &[u8]) -> Option<TokenFamily> {
+        Self::MARKERS.into_iter()
+            .find(|(_, marker)| code.windows(marker.len()).any(|window| window == *marker))
+            .map(|(family, _)| family)
+    }
+
+    /// Balance locations the family's template stores balances at,
+    /// in `contract` (the token, or the proxy in front of it)
+    pub fn predicted_locators(&self, contract: Address) -> Vec<StorageLocator> {
+        let slot = |slot: u64| B256::from(U256::from(slot));
+        let locators = match self {
+            TokenFamily::OpenZeppelin4 => vec![
+                StorageLocator::new(contract, slot(0), EvmLanguage::Solidity),
+                // Initializable and the `ContextUpgradeable` gap come first
+                StorageLocator::new(contract, slot(51), EvmLanguage::Solidity),
+            ],
+            TokenFamily::OpenZeppelin5 => vec![
+                StorageLocator::new(contract, slot(0), EvmLanguage::Solidity),
+            ],
+            TokenFamily::OpenZeppelin5Upgradeable => vec![
+                StorageLocator::new(contract, OZ_ERC20_STORAGE_LOCATION, EvmLanguage::Solidity),
+            ],
+            TokenFamily::Solmate => vec![
+                StorageLocator::new(contract, slot(3), EvmLanguage::Solidity),
+            ],
+            TokenFamily::Solady => vec![
+                StorageLocator::new(contract, slot(SOLADY_BALANCE_SEED), EvmLanguage::Seeded),
+            ],
+            TokenFamily::Vyper => (0..6)
+                .map(|idx| StorageLocator::new(contract, slot(idx), EvmLanguage::Vyper))
+                .collect(),
+        };
+        locators.into_iter()
+            .map(|mut locator| {
+                locator.family = Some(*self);
+                locator
+            })
+            .collect()
+    }
+
+}
+
+impl std::fmt::Display for TokenFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenFamily::OpenZeppelin4 => write!(f, "openzeppelin-4"),
+            TokenFamily::OpenZeppelin5 => write!(f, "openzeppelin-5"),
+            TokenFamily::OpenZeppelin5Upgradeable => write!(f, "openzeppelin-5-upgradeable"),
+            TokenFamily::Solmate => write!(f, "solmate"),
+            TokenFamily::Solady => write!(f, "solady"),
+            TokenFamily::Vyper => write!(f, "vyper"),
+        }
+    }
+}
+
+/// Family of `token`, classified from its own code or, for EIP-1967
+/// proxies, from the code of its implementation
+pub async fn fingerprint_token<P, T>(
+    provider: &P,
+    token: Address,
+    overrides: &StateOverride,
+    block: BlockNumberOrTag,
+) -> Result<Option<TokenFamily>>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let code = state::code_at_with_overrides(provider, overrides, token, block).await?;
+    if let Some(family) = TokenFamily::from_code(&code) {
+        return Ok(Some(family));
+    }
+    let implementation = state::storage_at_with_overrides(
+        provider,
+        overrides,
+        token,
+        EIP1967_IMPLEMENTATION_SLOT,
+        block,
+    ).await?;
+    if implementation.is_zero() {
+        return Ok(None);
+    }
+    let implementation = Address::from_word(implementation);
+    let code = state::code_at_with_overrides(provider, overrides, implementation, block).await?;
+    Ok(TokenFamily::from_code(&code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_family_from_markers() {
+        let code = |marker: &[u8]| [&[0x60, 0x80, 0x60, 0x40, 0x52][..], marker, &[0x00]].concat();
+        assert_eq!(TokenFamily::from_code(&code(&SOLADY_MARKER)), Some(TokenFamily::Solady));
+        assert_eq!(TokenFamily::from_code(&code(OZ4_MARKER)), Some(TokenFamily::OpenZeppelin4));
+        assert_eq!(TokenFamily::from_code(&code(SOLMATE_MARKER)), Some(TokenFamily::Solmate));
+        assert_eq!(TokenFamily::from_code(&code(VYPER_MARKER)), Some(TokenFamily::Vyper));
+        assert_eq!(TokenFamily::from_code(&code(&[0x63, 0xde, 0xad, 0xbe, 0xef])), None);
+        // Namespaced storage takes precedence over the shared custom errors
+        let oz5_upgradeable = [code(&OZ5_MARKER), OZ_NAMESPACED_MARKER.to_vec()].concat();
+        assert_eq!(TokenFamily::from_code(&oz5_upgradeable), Some(TokenFamily::OpenZeppelin5Upgradeable));
+    }
+
+    #[test]
+    fn test_predicted_locators() {
+        let token = Address::with_last_byte(0xaa);
+        let predicted = TokenFamily::Solady.predicted_locators(token);
+        assert_eq!(predicted.len(), 1);
+        assert_eq!(predicted[0].lang, EvmLanguage::Seeded);
+        assert_eq!(predicted[0].family, Some(TokenFamily::Solady));
+        assert_eq!(predicted[0].slot, B256::from(U256::from(SOLADY_BALANCE_SEED)));
+
+        let predicted = TokenFamily::OpenZeppelin5Upgradeable.predicted_locators(token);
+        assert_eq!(predicted[0].slot, OZ_ERC20_STORAGE_LOCATION);
+        assert_eq!(&OZ_NAMESPACED_MARKER[1..], OZ_ERC20_STORAGE_LOCATION.as_slice());
+    }
+
+}
@@ -0,0 +1,151 @@
+use crate::common::*;
+
+
+const BALANCEOF_4BYTE: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+const ALLOWANCE_4BYTE: [u8; 4] = [0xdd, 0x62, 0xed, 0x3e];
+const OWNEROF_4BYTE: [u8; 4] = [0x63, 0x52, 0x21, 0x1e];
+const TOTALSUPPLY_4BYTE: [u8; 4] = [0x18, 0x16, 0x0d, 0xdd];
+// Shared by ERC-1155 and ERC-6909
+const MULTI_BALANCEOF_4BYTE: [u8; 4] = [0x00, 0xfd, 0xd5, 0x8e];
+
+/// Static argument of a view getter, ABI-encoded as a single word
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GetterArg {
+    Address(Address),
+    Uint(U256),
+    Bytes32(B256),
+}
+
+impl GetterArg {
+
+    pub fn into_word(self) -> B256 {
+        match self {
+            GetterArg::Address(address) => address.into_word(),
+            GetterArg::Uint(value) => value.into(),
+            GetterArg::Bytes32(word) => word,
+        }
+    }
+
+}
+
+impl From<Address> for GetterArg {
+    fn from(address: Address) -> Self {
+        GetterArg::Address(address)
+    }
+}
+
+impl From<U256> for GetterArg {
+    fn from(value: U256) -> Self {
+        GetterArg::Uint(value)
+    }
+}
+
+impl From<B256> for GetterArg {
+    fn from(word: B256) -> Self {
+        GetterArg::Bytes32(word)
+    }
+}
+
+/// View call whose return value is read from a mapping keyed by its arguments
+/// (eg. `balanceOf(address)`, `nonces(address)` or `ownerOf(uint256)`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Getter {
+    pub selector: [u8; 4],
+    /// ABI-encoded call arguments
+    pub args: Vec<B256>,
+    /// Mapping keys, ordered from the outermost mapping in
+    pub keys: Vec<B256>,
+}
+
+impl Getter {
+
+    /// Getter for `selector` called with `args`, each arg keying one mapping level
+    pub fn new(selector: [u8; 4], args: Vec<GetterArg>) -> Self {
+        let args: Vec<_> = args.into_iter().map(GetterArg::into_word).collect();
+        Self { selector, keys: args.clone(), args }
+    }
+
+    /// Mapping keys for getters whose storage is keyed in a different 
+    /// order than the call arguments (eg. `_balances[id][account]`)
+    pub fn with_keys(mut self, keys: Vec<B256>) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    pub fn balance_of(holder: Address) -> Self {
+        Self::new(BALANCEOF_4BYTE, vec![holder.into()])
+    }
+
+    pub fn allowance(owner: Address, spender: Address) -> Self {
+        Self::new(ALLOWANCE_4BYTE, vec![owner.into(), spender.into()])
+    }
+
+    /// Positions of the mapping keys among the call arguments, 
+    /// empty when both follow the same order
+    pub fn key_order(&self) -> Vec<u8> {
+        if self.keys == self.args {
+            return Vec::new();
+        }
+        self.keys.iter()
+            .filter_map(|key| self.args.iter().position(|arg| arg == key))
+            .map(|idx| idx as u8)
+            .collect()
+    }
+
+    /// ERC20 `totalSupply()`, a plain storage word rather than a mapping
+    pub fn total_supply() -> Self {
+        Self::new(TOTALSUPPLY_4BYTE, Vec::new())
+    }
+
+    /// ERC-721 `ownerOf(id)`
+    pub fn owner_of(id: U256) -> Self {
+        Self::new(OWNEROF_4BYTE, vec![id.into()])
+    }
+
+    /// ERC-1155 / ERC-6909 `balanceOf(owner, id)`, keyed as `balances[owner][id]`
+    pub fn multi_balance_of(owner: Address, id: U256) -> Self {
+        Self::new(MULTI_BALANCEOF_4BYTE, vec![owner.into(), id.into()])
+    }
+
+    /// Calldata for the getter call
+    pub fn input(&self) -> Bytes {
+        self.args.iter()
+            .fold(self.selector.to_vec(), |mut input, key| {
+                input.extend_from_slice(key.as_slice());
+                input
+            })
+            .into()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_encoding() {
+        let holder = Address::with_last_byte(0xab);
+        let expected = format!("70a08231{}ab", "0".repeat(62));
+        assert_eq!(hex::encode(Getter::balance_of(holder).input()), expected);
+
+        let getter = Getter::new([0x63, 0x52, 0x21, 0x1e], vec![U256::from(7).into()]);
+        assert_eq!(getter.input().len(), 36);
+        assert_eq!(getter.input()[35], 7);
+        assert_eq!(getter.keys, vec![B256::from(U256::from(7))]);
+        assert_eq!(getter, Getter::owner_of(U256::from(7)));
+    }
+
+    #[test]
+    fn test_keys_reorder_keeps_input() {
+        let owner = Address::with_last_byte(1);
+        let id = U256::from(5);
+        let getter = Getter::multi_balance_of(owner, id);
+        let reordered = getter.clone().with_keys(vec![id.into(), owner.into_word()]);
+        assert_eq!(reordered.input(), getter.input());
+        assert_eq!(reordered.keys, vec![B256::from(id), owner.into_word()]);
+        assert_eq!(reordered.key_order(), vec![1, 0]);
+        assert!(getter.key_order().is_empty());
+    }
+
+}
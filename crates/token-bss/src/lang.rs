@@ -1,20 +1,93 @@
+use serde::{Serialize, Deserialize};
 use crate::common::*;
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Key scheme used to derive a mapping location from its slot and keys.
+/// Besides the compiler defaults it covers hand-rolled layouts of 
+/// gas-optimised tokens, for which `slot` is not necessarily a slot index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EvmLanguage {
+    /// `keccak(key ++ slot)`
     Solidity,
+    /// `keccak(slot ++ key)`
     Vyper,
+    /// `keccak(abi.encodePacked(address(key), slot))`, 52-byte preimage
+    Packed,
+    /// Solady's `keccak(address(key) ++ uint96(seed))`, with `slot` holding 
+    /// the seed and further keys appended as addresses (eg. allowances)
+    Seeded,
+    /// No hashing, the location is `uint160(key) + slot`
+    Unhashed,
+    /// `slot` is the storage key itself, found by poking storage for 
+    /// specific keys rather than derived from them
+    Raw,
 }
 
 impl EvmLanguage {
 
+    /// All schemes, in the order they are tried against a trace
+    pub const ALL: [EvmLanguage; 5] = [
+        EvmLanguage::Solidity,
+        EvmLanguage::Vyper,
+        EvmLanguage::Packed,
+        EvmLanguage::Seeded,
+        EvmLanguage::Unhashed,
+    ];
+
     pub fn mapping_loc(&self, slot: B256, holder: Address) -> B256 {
-        let holder: B256 = holder.into_word(); 
-         match &self {
-            EvmLanguage::Solidity => Self::solidity_mapping_loc(&slot, &holder),
-            EvmLanguage::Vyper => Self::vyper_mapping_loc(&slot, &holder),
+        self.nested_mapping_loc(slot, &[holder.into_word()])
+    }
+
+    /// Location in nested mappings, with `keys` ordered from the outermost 
+    /// mapping in (eg. `[owner, spender]` for `allowance[owner][spender]`)
+    pub fn nested_mapping_loc(&self, slot: B256, keys: &[B256]) -> B256 {
+        match &self {
+            EvmLanguage::Seeded => Self::seeded_mapping_loc(&slot, keys),
+            EvmLanguage::Raw => slot,
+            _ => keys.iter().fold(slot, |loc, key| match &self {
+                EvmLanguage::Solidity => Self::solidity_mapping_loc(&loc, key),
+                EvmLanguage::Vyper => Self::vyper_mapping_loc(&loc, key),
+                EvmLanguage::Packed => Self::packed_mapping_loc(&loc, key),
+                _ => U256::from_be_bytes(loc.0).wrapping_add(U256::from_be_bytes(key.0)).into(),
+            }),
+        }
+    }
+
+    /// Location one mapping level in, given the `preimage` of `loc` and the `key`
+    /// expected in it (not applicable to seeded and unhashed schemes)
+    pub(crate) fn inner_loc(&self, preimage: &[u8], key: &B256) -> Option<B256> {
+        let inner = match (self, preimage.len()) {
+            (EvmLanguage::Solidity, 64) if preimage[..32] == key[..] => &preimage[32..],
+            (EvmLanguage::Vyper, 64) if preimage[32..] == key[..] => &preimage[..32],
+            (EvmLanguage::Packed, 52) if is_address_word(key) 
+                && preimage[..20] == key[12..] => &preimage[20..],
+            _ => return None,
+        };
+        Some(B256::from_slice(inner))
+    }
+
+    /// Seed of a seeded location, given its `preimage` and all of its `keys`
+    pub(crate) fn seed(preimage: &[u8], keys: &[B256]) -> Option<B256> {
+        let (first, rest) = keys.split_first()?;
+        if preimage.len() != 20 * keys.len() + 12 || !keys.iter().all(is_address_word) {
+            return None;
         }
+        let rest_match = preimage[32..].chunks(20)
+            .zip(rest)
+            .all(|(chunk, key)| chunk == &key[12..]);
+        if preimage[..20] != first[12..] || !rest_match {
+            return None;
+        }
+        let mut seed = B256::ZERO;
+        seed[20..].copy_from_slice(&preimage[20..32]);
+        Some(seed)
+    }
+
+    /// Location of a struct member `offset` slots into `mapping(address => Struct)`
+    pub fn struct_field_loc(&self, slot: B256, holder: Address, offset: u64) -> B256 {
+        let base: U256 = self.mapping_loc(slot, holder).into();
+        base.wrapping_add(U256::from(offset)).into()
     }
 
     pub fn solidity_mapping_loc(storage_index: &FixedBytes<32>, key: &FixedBytes<32>) -> B256 {
@@ -25,6 +98,22 @@ impl EvmLanguage {
         Self::mapping_loc_from_tokens(storage_index, key)
     }
 
+    pub fn packed_mapping_loc(storage_index: &FixedBytes<32>, key: &FixedBytes<32>) -> B256 {
+        let hashable = [&key[12..], storage_index.as_slice()].concat();
+        alloy_utils::keccak256(hashable)
+    }
+
+    pub fn seeded_mapping_loc(seed: &FixedBytes<32>, keys: &[B256]) -> B256 {
+        let mut hashable = Vec::with_capacity(20 * keys.len() + 12);
+        for (i, key) in keys.iter().enumerate() {
+            hashable.extend_from_slice(&key[12..]);
+            if i == 0 {
+                hashable.extend_from_slice(&seed[20..]);
+            }
+        }
+        alloy_utils::keccak256(hashable)
+    }
+
     fn mapping_loc_from_tokens(token_0: &FixedBytes<32>, token_1: &FixedBytes<32>) -> B256 {
         let hashable = [token_0.0.to_vec(), token_1.0.to_vec()].concat();
         alloy_utils::keccak256(hashable)
@@ -32,11 +121,20 @@ impl EvmLanguage {
 
 }
 
+// Keys hashed as 20 bytes have to be addresses to be recoverable
+fn is_address_word(key: &B256) -> bool {
+    key[..12].iter().all(|b| *b == 0)
+}
+
 impl std::fmt::Display for EvmLanguage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             EvmLanguage::Solidity => write!(f, "solidity"),
             EvmLanguage::Vyper => write!(f, "vyper"),
+            EvmLanguage::Packed => write!(f, "packed"),
+            EvmLanguage::Seeded => write!(f, "seeded"),
+            EvmLanguage::Unhashed => write!(f, "unhashed"),
+            EvmLanguage::Raw => write!(f, "raw"),
         }
     }
 }
@@ -44,10 +142,14 @@ impl std::fmt::Display for EvmLanguage {
 impl std::str::FromStr for EvmLanguage {
     type Err = eyre::Error;
 
-    fn from_str(s: &str) -> Result<Self> {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
             "solidity" => Ok(EvmLanguage::Solidity),
             "vyper" => Ok(EvmLanguage::Vyper),
+            "packed" => Ok(EvmLanguage::Packed),
+            "seeded" => Ok(EvmLanguage::Seeded),
+            "unhashed" => Ok(EvmLanguage::Unhashed),
+            "raw" => Ok(EvmLanguage::Raw),
             _ => Err(eyre::eyre!("Invalid language")),
         }
     }
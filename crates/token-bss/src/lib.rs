@@ -1,36 +1,115 @@
 mod trace_parser;
+mod access_list_parser;
+mod strategy;
+mod verification;
+mod ratio;
+mod capabilities;
+mod error;
 mod slot_finder;
+mod allowance;
+mod nft;
+mod setter;
+mod balance_setter;
+mod writer;
+mod getter;
+mod tracer;
+mod revm_tracer;
+mod bytecode;
+mod symbolic;
+mod fingerprint;
+mod proxy;
+mod storage_layout;
+mod locator;
 mod lang;
 mod utils;
 mod ops;
+#[cfg(test)]
+mod test_utils;
 
 
-pub use slot_finder::find_balance_slots_and_update_ratio;
+pub use slot_finder::{find_balance_slots_and_update_ratio, SlotFinder, SlotSearch};
+pub use locator::{StorageLocator, StorageWrite, IndirectKey, STORAGE_LOCATOR_VERSION};
+pub use ratio::UpdateRatio;
+pub use allowance::{find_allowance_slot, set_allowance};
+pub use nft::{
+    find_owner_slot, set_owner, set_nft_balance, 
+    find_multi_token_slot, set_multi_token_balance,
+};
+pub use setter::{
+    set_getter_value, write_getter_value, set_getter_value_exact, 
+    set_balance_exact, solve_stored_value, ExactWrite,
+};
+pub use balance_setter::{BalanceSetter, BalanceWrite};
+pub use writer::{
+    StorageWriter, AnvilStorageWriter, HardhatStorageWriter, 
+    GanacheStorageWriter, TenderlyStorageWriter,
+};
+pub use getter::{Getter, GetterArg};
+pub use strategy::Strategy;
+pub use capabilities::Capabilities;
 pub use lang::EvmLanguage;
+pub use tracer::{Tracer, RpcTracer, LocalTracer, RecordedTracer};
+pub use revm_tracer::RevmTracer;
+pub use bytecode::{find_bytecode_slots, find_bytecode_balance_slots};
+pub use fingerprint::{TokenFamily, fingerprint_token};
+pub use proxy::{ProxyKind, ProxyInfo, resolve_proxy, is_upgraded};
+pub use storage_layout::{StorageLayout, StorageEntry, StorageType, LayoutCheck, check_storage_layout};
+pub use trace_parser::TraceEvent;
+pub use error::{Error, Result};
 
 mod common;
 
-pub use common::TraceFn;
 use common::*;
 
 pub async fn find_slot<P, T>(
     provider: &P, 
     token: Address, 
     holder: Option<Address>,
-    trace_fn: Option<TraceFn>,
-) -> Result<(Address, B256, f64, String)> 
+    tracer: impl Tracer,
+) -> Result<StorageLocator> 
     where P: Provider<T>, T: Transport + Clone
 {
-    let holder = holder.unwrap_or_else(default_holder);
-    slot_finder::find_balance_slots_and_update_ratio(
-        provider, 
-        holder, 
-        token,
-        trace_fn,
-    ).await
+    SlotFinder::new(provider, token)
+        .with_holder(holder.unwrap_or_else(default_holder))
+        .with_tracer(tracer)
+        .find().await
+        .map(|search| search.best)
+}
+
+/// Locate the storage word backing the return value of `getter` on `contract`
+pub async fn find_getter_slot<P, T>(
+    provider: &P, 
+    contract: Address, 
+    getter: Getter,
+    tracer: impl Tracer,
+) -> Result<StorageLocator> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    SlotFinder::new(provider, contract)
+        .with_getter(getter)
+        .with_tracer(tracer)
+        .find().await
+        .map(|search| search.best)
+}
+
+/// Locate the storage word behind `totalSupply()` of `token`. It isn't keyed
+/// by anything, so every slot read by the call is poked in turn.
+pub async fn find_total_supply_slot<P, T>(
+    provider: &P, 
+    token: Address, 
+    tracer: impl Tracer,
+) -> Result<StorageLocator> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    SlotFinder::new(provider, token)
+        .with_getter(Getter::total_supply())
+        .with_strategy(Strategy::Poke)
+        .with_tracer(tracer)
+        .find().await
+        .map(|search| search.best)
 }
 
 // Avoid zero address for holder
 fn default_holder() -> Address {
     Address::from_word(B256::from(U256::from(1)))
-}
\ No newline at end of file
+}
@@ -0,0 +1,284 @@
+use serde::{Serialize, Deserialize};
+use super::{lang::EvmLanguage, strategy::Strategy, fingerprint::TokenFamily, proxy::ProxyInfo, ratio::UpdateRatio, utils};
+use crate::common::*;
+
+
+/// Bumped whenever the serialized shape of `StorageLocator` changes,
+/// so cached entries from older releases can be told apart.
+pub const STORAGE_LOCATOR_VERSION: u8 = 11;
+
+pub(crate) const WORD_BITS: u16 = 256;
+
+/// Where a token keeps the balance of its holders and how to derive
+/// the storage key for a specific holder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageLocator {
+    pub version: u8,
+    /// Contract whose storage holds the balances (not necessarily the token)
+    pub contract: Address,
+    /// Base slot of the balance mapping (the seed for seeded key schemes)
+    pub slot: B256,
+    /// Key derivation scheme for the mapping
+    pub lang: EvmLanguage,
+    /// Slots between the mapping location and the balance, for balances 
+    /// kept in a struct (eg. `mapping(address => Account)`)
+    pub slot_offset: u64,
+    /// Ratio between the reported balance and the stored value
+    pub update_ratio: UpdateRatio,
+    /// Share of the verification probes (holders and values written to the 
+    /// slot) that reported a balance linear in the stored value
+    pub confidence: f64,
+    /// Block at which the locator was discovered
+    pub block: u64,
+    /// Code hash of the storage contract at discovery
+    pub code_hash: B256,
+    /// Bit offset of the balance inside the storage word
+    pub offset: u16,
+    /// Bit width of the balance inside the storage word
+    pub width: u16,
+    /// Getter arguments in the order they key the nested mappings, outermost 
+    /// first (empty when it matches the argument order, eg. not for 
+    /// ERC-1155 `balanceOf(account, id)` stored as `_balances[id][account]`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_order: Vec<u8>,
+    /// Mapping the key is read from, for balances keyed by an id looked 
+    /// up from another mapping (`balances[ids[holder]]`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indirect: Option<IndirectKey>,
+    /// Keys (eg. the traced holder) a raw storage key is valid for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_keys: Vec<B256>,
+    /// Discovery strategy that found the location
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Known implementation the token was recognised as, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<TokenFamily>,
+    /// Proxy the token forwards the getter through, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyInfo>,
+}
+
+/// First hop of an indirect locator, in the same contract as the value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndirectKey {
+    pub slot: B256,
+    pub lang: EvmLanguage,
+}
+
+impl IndirectKey {
+
+    /// Storage key holding the id for mapping `keys`
+    pub fn storage_key_for(&self, keys: &[B256]) -> B256 {
+        self.lang.nested_mapping_loc(self.slot, keys)
+    }
+
+}
+
+/// Single storage slot write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageWrite {
+    pub contract: Address,
+    pub key: B256,
+    pub value: B256,
+}
+
+impl StorageLocator {
+
+    /// Raw storage `key` only valid for getter `keys`
+    pub fn raw(contract: Address, key: B256, keys: &[B256]) -> Self {
+        let mut locator = Self::new(contract, key, EvmLanguage::Raw);
+        locator.raw_keys = keys.to_vec();
+        locator
+    }
+
+    /// Locator for a full-word balance with one-to-one update ratio
+    pub fn new(contract: Address, slot: B256, lang: EvmLanguage) -> Self {
+        Self {
+            version: STORAGE_LOCATOR_VERSION,
+            contract,
+            slot,
+            lang,
+            slot_offset: 0,
+            update_ratio: UpdateRatio::ONE,
+            confidence: 1.0,
+            block: 0,
+            code_hash: B256::ZERO,
+            offset: 0,
+            width: WORD_BITS,
+            key_order: Vec::new(),
+            indirect: None,
+            raw_keys: Vec::new(),
+            strategy: Strategy::default(),
+            family: None,
+            proxy: None,
+        }
+    }
+
+    pub fn is_current_version(&self) -> bool {
+        self.version == STORAGE_LOCATOR_VERSION
+    }
+
+    /// Storage key holding the balance of `holder`
+    pub fn storage_key(&self, holder: Address) -> B256 {
+        self.lang.struct_field_loc(self.slot, holder, self.slot_offset)
+    }
+
+    /// Storage key for nested mapping `keys`, ordered from the outermost mapping in.
+    /// For indirect locators `keys` are the ids read through `indirect`, not the holder.
+    pub fn storage_key_for(&self, keys: &[B256]) -> B256 {
+        let loc: U256 = self.lang.nested_mapping_loc(self.slot, keys).into();
+        loc.wrapping_add(U256::from(self.slot_offset)).into()
+    }
+
+    /// Getter `args` rearranged into mapping key order
+    pub fn mapping_keys(&self, args: &[B256]) -> Vec<B256> {
+        if self.key_order.is_empty() {
+            return args.to_vec();
+        }
+        self.key_order.iter()
+            .filter_map(|&idx| args.get(idx as usize).copied())
+            .collect()
+    }
+
+    /// Write that stores `balance` as the raw value for `holder`, keeping 
+    /// the bits outside of the balance field from the `current` word
+    pub fn storage_write(&self, holder: Address, balance: U256, current: B256) -> Result<StorageWrite> {
+        self.storage_write_for(&[holder.into_word()], balance, current)
+    }
+
+    /// Same as `storage_write`, for nested mapping `keys`
+    pub fn storage_write_for(&self, keys: &[B256], value: U256, current: B256) -> Result<StorageWrite> {
+        if !self.raw_keys.is_empty() && self.raw_keys != keys {
+            return Err(Error::RawKeyMismatch);
+        }
+        Ok(StorageWrite {
+            contract: self.contract,
+            key: self.storage_key_for(keys),
+            value: self.pack(current, value)?,
+        })
+    }
+
+    pub fn is_packed(&self) -> bool {
+        self.width < WORD_BITS
+    }
+
+    /// Largest value the balance field can hold
+    pub fn max_value(&self) -> U256 {
+        utils::field_max(self.width)
+    }
+
+    /// Balance field of a storage word
+    pub fn unpack(&self, word: B256) -> U256 {
+        (U256::from_be_bytes(word.0) >> self.offset as usize) & self.max_value()
+    }
+
+    /// Replace the balance field of `current` with `value`
+    pub fn pack(&self, current: B256, value: U256) -> Result<B256> {
+        if value > self.max_value() {
+            return Err(Error::ValueOverflow(self.width));
+        }
+        let mask = self.max_value() << self.offset as usize;
+        let current = U256::from_be_bytes(current.0);
+        let word = (current & !mask) | (value << self.offset as usize);
+        Ok(word.into())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    fn locator(lang: EvmLanguage) -> StorageLocator {
+        StorageLocator::new(Address::ZERO, B256::from(U256::from(3)), lang)
+    }
+
+    #[test]
+    fn test_storage_key_matches_lang() {
+        let holder = Address::from_word(B256::from(U256::from(1)));
+        for lang in [EvmLanguage::Solidity, EvmLanguage::Vyper] {
+            let locator = locator(lang);
+            assert_eq!(locator.storage_key(holder), lang.mapping_loc(locator.slot, holder));
+        }
+    }
+
+    #[test]
+    fn test_nested_storage_key() {
+        let owner = Address::with_last_byte(1).into_word();
+        let spender = Address::with_last_byte(2).into_word();
+        let mut locator = locator(EvmLanguage::Solidity);
+        let inner = EvmLanguage::solidity_mapping_loc(&locator.slot, &owner);
+        let outer = EvmLanguage::solidity_mapping_loc(&inner, &spender);
+        assert_eq!(locator.storage_key_for(&[owner, spender]), outer);
+
+        locator.lang = EvmLanguage::Vyper;
+        let inner = EvmLanguage::vyper_mapping_loc(&locator.slot, &owner);
+        let outer = EvmLanguage::vyper_mapping_loc(&inner, &spender);
+        assert_eq!(locator.storage_key_for(&[owner, spender]), outer);
+        assert_eq!(locator.storage_key_for(&[owner]), locator.storage_key(Address::with_last_byte(1)));
+    }
+
+    #[test]
+    fn test_mapping_keys_follow_key_order() {
+        let account = Address::with_last_byte(1).into_word();
+        let id = B256::from(U256::from(9));
+        let mut locator = locator(EvmLanguage::Solidity);
+        assert_eq!(locator.mapping_keys(&[account, id]), vec![account, id]);
+
+        locator.key_order = vec![1, 0];
+        assert_eq!(locator.mapping_keys(&[account, id]), vec![id, account]);
+    }
+
+    #[test]
+    fn test_raw_write_only_for_its_keys() -> Result<()> {
+        let holder = Address::with_last_byte(1);
+        let key = B256::from(U256::from(77));
+        let locator = StorageLocator::raw(Address::ZERO, key, &[holder.into_word()]);
+
+        let write = locator.storage_write(holder, U256::from(5), B256::ZERO)?;
+        assert_eq!(write.key, key);
+        assert!(matches!(
+            locator.storage_write(Address::with_last_byte(2), U256::from(5), B256::ZERO),
+            Err(Error::RawKeyMismatch)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_roundtrip() -> Result<()> {
+        let locator = locator(EvmLanguage::Vyper);
+        let json = serde_json::to_string(&locator)?;
+        assert!(json.contains("\"lang\":\"vyper\""));
+        assert!(json.contains("\"updateRatio\":{\"numerator\":\"0x1\",\"denominator\":\"0x1\"}"));
+        let decoded: StorageLocator = serde_json::from_str(&json)?;
+        assert_eq!(decoded, locator);
+        Ok(())
+    }
+
+    #[test]
+    fn test_packed_write_keeps_neighbours() -> Result<()> {
+        let mut locator = locator(EvmLanguage::Solidity);
+        locator.offset = 96;
+        locator.width = 96;
+        let current: B256 = U256::MAX.into();
+        let holder = Address::with_last_byte(1);
+
+        let write = locator.storage_write(holder, U256::from(5), current)?;
+        let word = U256::from_be_bytes(write.value.0);
+
+        assert!(locator.is_packed());
+        assert_eq!(locator.unpack(write.value), U256::from(5));
+        assert_eq!(word & utils::field_max(96), utils::field_max(96));
+        assert_eq!(word >> 192, utils::field_max(64));
+        assert!(matches!(
+            locator.storage_write(holder, U256::from(1) << 96, current), 
+            Err(Error::ValueOverflow(96))
+        ));
+        Ok(())
+    }
+
+}
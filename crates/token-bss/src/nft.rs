@@ -0,0 +1,138 @@
+use super::{
+    locator::StorageLocator,
+    getter::Getter,
+    setter,
+    slot_finder::SlotFinder,
+    tracer::{Tracer, RpcTracer},
+};
+use crate::common::*;
+
+
+/// Locate the ERC-721 `ownerOf(id)` slot, `id` has to be minted
+/// since most implementations revert for unknown ids
+pub async fn find_owner_slot<P, T>(
+    provider: &P,
+    token: Address,
+    id: U256,
+    tracer: impl Tracer,
+) -> Result<StorageLocator>
+    where P: Provider<T>, T: Transport + Clone
+{
+    crate::find_getter_slot(provider, token, Getter::owner_of(id), tracer).await
+}
+
+/// Make `owner` the owner of ERC-721 token `id`, returning the reported owner.
+/// Balances of the previous and the new owner are left as they are.
+pub async fn set_owner<P, T>(
+    provider: &P,
+    token: Address,
+    id: U256,
+    owner: Address,
+    locator: Option<StorageLocator>,
+) -> Result<Address>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let locator = match locator {
+        Some(locator) => locator,
+        None => find_owner_slot(provider, token, id, RpcTracer).await?,
+    };
+    let getter = Getter::owner_of(id);
+    let value = owner.into_word().into();
+    let reported = setter::set_getter_value(provider, token, &getter, value, &locator).await?;
+    Ok(Address::from_word(reported.into()))
+}
+
+/// Set the ERC-721 `balanceOf(holder)`, returning the reported balance
+pub async fn set_nft_balance<P, T>(
+    provider: &P,
+    token: Address,
+    holder: Address,
+    amount: U256,
+    locator: Option<StorageLocator>,
+) -> Result<U256>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let locator = match locator {
+        Some(locator) => locator,
+        None => crate::find_slot(provider, token, Some(holder), RpcTracer).await?,
+    };
+    let getter = Getter::balance_of(holder);
+    setter::set_getter_value(provider, token, &getter, amount, &locator).await
+}
+
+/// Locate the ERC-1155 / ERC-6909 `balanceOf(owner, id)` slot. Both the
+/// `balances[id][owner]` (OpenZeppelin ERC-1155) and the `balances[owner][id]`
+/// (ERC-6909, Solmate) layouts are tried. Poking is off for the first one, as 
+/// it would match the traced owner's slot of an owner-first layout.
+pub async fn find_multi_token_slot<P, T>(
+    provider: &P,
+    token: Address,
+    owner: Option<Address>,
+    id: U256,
+    tracer: impl Tracer,
+) -> Result<StorageLocator>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let owner = owner.unwrap_or_else(crate::default_holder);
+    let getter = Getter::multi_balance_of(owner, id);
+    let id_first = getter.clone().with_keys(vec![id.into(), owner.into_word()]);
+    let id_first_search = SlotFinder::new(provider, token)
+        .with_getter(id_first)
+        .with_poke_fallback(false)
+        .with_tracer(&tracer)
+        .find().await
+        .map(|search| search.best);
+    match id_first_search {
+        Err(err) if err.is_not_found() => {
+            crate::find_getter_slot(provider, token, getter, &tracer).await
+        },
+        res => res,
+    }
+}
+
+/// Set the ERC-1155 / ERC-6909 `balanceOf(owner, id)`, returning the reported balance
+pub async fn set_multi_token_balance<P, T>(
+    provider: &P,
+    token: Address,
+    owner: Address,
+    id: U256,
+    amount: U256,
+    locator: Option<StorageLocator>,
+) -> Result<U256>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let locator = match locator {
+        Some(locator) => locator,
+        None => find_multi_token_slot(provider, token, Some(owner), id, RpcTracer).await?,
+    };
+    let getter = Getter::multi_balance_of(owner, id);
+    setter::set_getter_value(provider, token, &getter, amount, &locator).await
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{node_bindings::Anvil, providers::ReqwestProvider};
+    use super::*;
+    use eyre::Result;
+
+    #[tokio::test]
+    async fn test_owner_first_multi_token_slot() -> Result<()> {
+        let anvil = Anvil::new().spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let token = Address::with_last_byte(0xaa);
+        // ERC-6909 balanceOf(address,uint256) returning `balances[owner][id]` at slot 0
+        let code = Bytes::from(hex::decode(
+            "6004356000526000602052604060002060205260243560005260406000205460005260206000f3"
+        )?);
+        provider.client().request::<_, ()>("anvil_setCode", (token, code)).await?;
+        let owner = Address::with_last_byte(0xbb);
+
+        let locator = find_multi_token_slot(&provider, token, Some(owner), U256::from(7), RpcTracer).await?;
+
+        assert_eq!(locator.lang, crate::EvmLanguage::Solidity);
+        assert_eq!(locator.slot, B256::ZERO);
+        assert!(locator.key_order.is_empty());
+        Ok(())
+    }
+
+}
@@ -0,0 +1,17 @@
+use alloy::rpc::types::eth::{AccessList, AccessListWithGasUsed};
+use crate::common::*;
+
+
+/// Storage touched by `call_request`, without needing the debug namespace
+pub async fn create_access_list<P, T>(
+    provider: &P,
+    call_request: &TransactionRequest,
+    block: BlockNumberOrTag,
+) -> Result<AccessList> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let response: AccessListWithGasUsed = provider.client()
+        .request("eth_createAccessList", (call_request, block))
+        .await?;
+    Ok(response.access_list)
+}
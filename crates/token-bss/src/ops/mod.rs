@@ -1,2 +1,4 @@
+pub mod access_list;
+pub mod state;
 pub mod token;
 pub mod trace;
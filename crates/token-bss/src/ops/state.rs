@@ -0,0 +1,120 @@
+use alloy::rpc::types::eth::state::StateOverride;
+use crate::{common::*, locator::StorageLocator};
+
+
+pub async fn code_hash<P, T, N>(
+    provider: &P,
+    contract: Address,
+    block: BlockNumberOrTag,
+) -> Result<B256> 
+    where P: Provider<T, N>, T: Transport + Clone, N: Network
+{
+    let code = code_at(provider, contract, block).await?;
+    Ok(alloy_utils::keccak256(code))
+}
+
+pub async fn code_at<P, T, N>(
+    provider: &P,
+    contract: Address,
+    block: BlockNumberOrTag,
+) -> Result<Bytes> 
+    where P: Provider<T, N>, T: Transport + Clone, N: Network
+{
+    let code: Bytes = provider.client()
+        .request("eth_getCode", (contract, block))
+        .await?;
+    Ok(code)
+}
+
+/// Runtime code of `contract`, or its code in `overrides`
+pub async fn code_at_with_overrides<P, T, N>(
+    provider: &P,
+    overrides: &StateOverride,
+    contract: Address,
+    block: BlockNumberOrTag,
+) -> Result<Bytes> 
+    where P: Provider<T, N>, T: Transport + Clone, N: Network
+{
+    match overrides.get(&contract).and_then(|account| account.code.clone()) {
+        Some(code) => Ok(code),
+        None => code_at(provider, contract, block).await,
+    }
+}
+
+pub async fn storage_at<P, T, N>(
+    provider: &P,
+    contract: Address,
+    key: B256,
+    block: BlockNumberOrTag,
+) -> Result<B256> 
+    where P: Provider<T, N>, T: Transport + Clone, N: Network
+{
+    let value: B256 = provider.client()
+        .request("eth_getStorageAt", (contract, key, block))
+        .await?;
+    Ok(value)
+}
+
+/// Code hash of `contract`, or of its code in `overrides`
+pub async fn code_hash_with_overrides<P, T, N>(
+    provider: &P,
+    overrides: &StateOverride,
+    contract: Address,
+    block: BlockNumberOrTag,
+) -> Result<B256> 
+    where P: Provider<T, N>, T: Transport + Clone, N: Network
+{
+    match overrides.get(&contract).and_then(|account| account.code.as_ref()) {
+        Some(code) => Ok(alloy_utils::keccak256(code)),
+        None => code_hash(provider, contract, block).await,
+    }
+}
+
+/// Storage of `contract` at `key`, as seen by calls with `overrides` applied
+pub async fn storage_at_with_overrides<P, T, N>(
+    provider: &P,
+    overrides: &StateOverride,
+    contract: Address,
+    key: B256,
+    block: BlockNumberOrTag,
+) -> Result<B256> 
+    where P: Provider<T, N>, T: Transport + Clone, N: Network
+{
+    match overridden_storage(overrides, contract, key) {
+        Some(value) => Ok(value),
+        None => storage_at(provider, contract, key, block).await,
+    }
+}
+
+/// Value `overrides` set for the storage at `key`, zero for anything 
+/// missing from a full storage override
+pub fn overridden_storage(overrides: &StateOverride, contract: Address, key: B256) -> Option<B256> {
+    let account = overrides.get(&contract)?;
+    match (&account.state, &account.state_diff) {
+        (Some(state), _) => Some(state.get(&key).copied().unwrap_or_default()),
+        (None, Some(state_diff)) => state_diff.get(&key).copied(),
+        (None, None) => None,
+    }
+}
+
+/// Mapping keys of `locator` for getter keys `keys`, reading the id 
+/// first for indirect locators
+pub async fn resolve_keys<P, T, N>(
+    provider: &P,
+    locator: &StorageLocator,
+    keys: &[B256],
+    overrides: &StateOverride,
+    block: BlockNumberOrTag,
+) -> Result<Vec<B256>> 
+    where P: Provider<T, N>, T: Transport + Clone, N: Network
+{
+    let Some(indirect) = locator.indirect else {
+        return Ok(keys.to_vec());
+    };
+    let id_key = indirect.storage_key_for(keys);
+    let id = storage_at_with_overrides(provider, overrides, locator.contract, id_key, block).await?;
+    if id.is_zero() {
+        return Err(Error::UnresolvedIndirectKey);
+    }
+    Ok(vec![id])
+}
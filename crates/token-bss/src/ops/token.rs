@@ -1,59 +1,90 @@
 // ! Necessary to set gas for calls otherwise changing the wrong storage could 
 // ! cause time-out eg. 0xf25c91c87e0b1fd9b4064af0f427157aab0193a7(Ethereum)
 
-use alloy::rpc::types::eth::state::AccountOverride;
-use std::{collections::HashMap, str::FromStr};
+use alloy::rpc::types::eth::state::StateOverride;
+use std::collections::HashMap;
 use super::super::utils;
 use crate::common::*;
 
 
-const BALANCEOF_4BYTE: &str = "0x70a08231";
-const CALL_GAS_LIMIT: u128 = 200_000;
+pub const CALL_GAS_LIMIT: u128 = 200_000;
+const DECIMALS_4BYTE: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
 
 pub async fn call_request<P, T>(
     provider: &P,
     call_request: &TransactionRequest,
+    block: BlockNumberOrTag,
 ) -> Result<U256> 
     where P: Provider<T>, T: Transport + Clone
 {
-    let balance = provider.call(call_request).await?;
+    let balance = provider.call(call_request).block(block.into()).await?;
     let balance = utils::bytes_to_u256(balance);
     Ok(balance)
 }
 
+/// Call with `overrides` applied, eg. the code of a token not deployed yet
+pub async fn call_request_with_overrides<P, T>(
+    provider: &P,
+    call_request: &TransactionRequest,
+    overrides: &StateOverride,
+    block: BlockNumberOrTag,
+) -> Result<U256> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    // Not every provider takes an override set, so it is only sent if needed
+    if overrides.is_empty() {
+        return call_request(provider, call_request, block).await;
+    }
+    let bal = provider.call(call_request)
+        .overrides(overrides)
+        .block(block.into())
+        .await?;
+    Ok(utils::bytes_to_u256(bal))
+}
+
+/// Call with `overrides` applied and the storage at `map_loc` set to `new_slot_val`
 pub async fn call_request_with_storage_overrides<P, T>(
     provider: &P,
     call_request: &TransactionRequest,
+    overrides: &StateOverride,
     storage_contract: Address,
     map_loc: B256,
     new_slot_val: B256,
+    block: BlockNumberOrTag,
 ) -> Result<U256> 
     where P: Provider<T>, T: Transport + Clone
 {
-    let state_diff: HashMap<_, _> = [(map_loc, new_slot_val)].into_iter().collect();
-    let account_override = AccountOverride {
-        state_diff: Some(state_diff),
-        ..AccountOverride::default()
+    let mut state_override = overrides.clone();
+    let account_override = state_override.entry(storage_contract).or_default();
+    // A full storage override replaces the storage, so the slot has to go into it
+    match account_override.state.as_mut() {
+        Some(state) => state.insert(map_loc, new_slot_val),
+        None => account_override.state_diff
+            .get_or_insert_with(HashMap::new)
+            .insert(map_loc, new_slot_val),
     };
-    let state_override: HashMap<_, _> = 
-        [(storage_contract, account_override)].into_iter().collect();
-
-    let bal = provider.call(call_request).overrides(&state_override).await?;
-    Ok(utils::bytes_to_u256(bal))
+    call_request_with_overrides(provider, call_request, &state_override, block).await
 }
 
-pub fn balanceof_call_req(holder: Address, token: Address) -> Result<TransactionRequest> {
-    let call_req = TransactionRequest::default()
-        .with_input(balanceof_input_data(holder)?)
-        .with_gas_limit(CALL_GAS_LIMIT)
-        .with_from(holder)
+pub async fn decimals<P, T>(
+    provider: &P,
+    token: Address,
+    block: BlockNumberOrTag,
+) -> Result<u8> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let req = TransactionRequest::default()
+        .with_input(Bytes::from(DECIMALS_4BYTE.to_vec()))
         .with_to(token.into());
-    Ok(call_req)
+    let decimals = call_request(provider, &req, block).await?;
+    u8::try_from(decimals)
+        .map_err(|_| eyre::eyre!("Invalid decimals {decimals} for {token:?}").into())
 }
 
-fn balanceof_input_data(holder: Address) -> Result<Bytes> {
-    let holder = format!("{:?}", holder)[2..].to_string();
-    let data_str = format!("{BALANCEOF_4BYTE}000000000000000000000000{holder}");
-    let data = Bytes::from_str(&data_str)?;
-    Ok(data)
-}
\ No newline at end of file
+pub fn call_req(from: Address, to: Address, input: Bytes) -> TransactionRequest {
+    TransactionRequest::default()
+        .with_input(input)
+        .with_gas_limit(CALL_GAS_LIMIT)
+        .with_from(from)
+        .with_to(to.into())
+}
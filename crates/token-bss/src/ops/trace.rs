@@ -1,8 +1,7 @@
-use crate::common::*;
-use alloy::{
-    providers::ext::DebugApi,
-    transports::TransportErrorKind,
-    rpc::types::trace::geth::{
+use crate::{common::*, tracer::Tracer};
+use alloy::rpc::types::{
+    eth::state::StateOverride,
+    trace::geth::{
         DefaultFrame, GethDebugTracingOptions, 
         GethDefaultTracingOptions, GethTrace,
         GethDebugTracingCallOptions,
@@ -10,47 +9,42 @@ use alloy::{
 };
 
 
-pub async fn default_trace_call<P, T, N>(
+pub async fn default_trace_call<P, T, Tr>(
     provider: &P,
+    tracer: &Tr,
     call_request: TransactionRequest, 
     block: Option<BlockNumberOrTag>, 
-    trace_fn: Option<TraceFn>,
+    overrides: StateOverride,
 ) -> Result<DefaultFrame> 
     where 
-        P: Provider<T, N>, 
+        P: Provider<T>, 
         T: Transport + Clone, 
-        N: Network,
+        Tr: Tracer + ?Sized,
 {
     let mut tracing_options = GethDebugTracingOptions::default();
     tracing_options.config = GethDefaultTracingOptions::default()
         .with_disable_memory(false)
         .with_enable_memory(true)
         .with_disable_stack(false);
-    let trace_call_opt = GethDebugTracingCallOptions::default()
+    let mut trace_call_opt = GethDebugTracingCallOptions::default()
         .with_tracing_options(tracing_options);
-    let response = match trace_fn {
-        None => provider.debug_trace_call(
-            call_request, 
-            block.unwrap_or(BlockNumberOrTag::Latest), 
-            trace_call_opt,
-        ).await,
-        Some(trace_fn) => {
-            let header = provider.get_block(block.unwrap_or_default().into(), false).await?.unwrap().header;
-            trace_fn(
-                call_request, 
-                header, 
-                trace_call_opt,
-            ).map_err(|err| TransportErrorKind::custom_str(&err.to_string()))
-        }
-    }?;
+    if !overrides.is_empty() {
+        trace_call_opt.state_overrides = Some(overrides);
+    }
+    let response = tracer.trace_call(
+        provider,
+        call_request, 
+        block.unwrap_or(BlockNumberOrTag::Latest), 
+        trace_call_opt,
+    ).await?;
 
     match response {
         GethTrace::Default(trace) if trace.failed => {
-            Err(eyre::eyre!("traceCall failed"))
+            Err(Error::TraceCallFailed("call reverted".to_string()))
         },
         GethTrace::Default(trace) if !trace.failed => {
             Ok(trace)
         },
-        _ => Err(eyre::eyre!("Only default traces supported")),
+        _ => Err(Error::TraceUnsupported),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,174 @@
+use alloy::{primitives::b256, rpc::types::eth::state::StateOverride};
+use serde::{Serialize, Deserialize};
+use super::{locator::StorageLocator, ops::{state, token}};
+use crate::common::*;
+
+
+// `keccak256("eip1967.proxy.implementation") - 1`
+pub(crate) const EIP1967_IMPLEMENTATION_SLOT: B256 = b256!("360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc");
+// `keccak256("eip1967.proxy.beacon") - 1`
+const EIP1967_BEACON_SLOT: B256 = b256!("a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50");
+// `keccak256("PROXIABLE")`
+const EIP1822_PROXIABLE_SLOT: B256 = b256!("c5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7");
+const IMPLEMENTATION_4BYTE: [u8; 4] = [0x5c, 0x60, 0xda, 0x1b];
+const FACET_ADDRESS_4BYTE: [u8; 4] = [0xcd, 0xff, 0xac, 0xc6];
+
+/// Proxy pattern a token call is forwarded through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProxyKind {
+    /// Implementation in the EIP-1967 implementation slot
+    Eip1967,
+    /// Implementation read from the beacon in the EIP-1967 beacon slot
+    Beacon,
+    /// Implementation in the EIP-1822 (UUPS) `PROXIABLE` slot
+    Eip1822,
+    /// EIP-2535 diamond, with the getter routed to a facet
+    Diamond,
+}
+
+impl std::fmt::Display for ProxyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyKind::Eip1967 => write!(f, "eip1967"),
+            ProxyKind::Beacon => write!(f, "beacon"),
+            ProxyKind::Eip1822 => write!(f, "eip1822"),
+            ProxyKind::Diamond => write!(f, "diamond"),
+        }
+    }
+}
+
+/// Code a proxied token runs at discovery. The code hash changing means the
+/// proxy was upgraded and the storage layout may have changed with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyInfo {
+    pub kind: ProxyKind,
+    /// Implementation, or the facet for diamonds
+    pub implementation: Address,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beacon: Option<Address>,
+    /// Selector the facet was looked up for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<FixedBytes<4>>,
+    pub implementation_code_hash: B256,
+}
+
+/// Proxy `token` forwards calls with `selector` through, if any. Standard
+/// slots are checked first, then EIP-2535 `facetAddress(selector)`.
+pub async fn resolve_proxy<P, T>(
+    provider: &P,
+    token: Address,
+    selector: [u8; 4],
+    overrides: &StateOverride,
+    block: BlockNumberOrTag,
+) -> Result<Option<ProxyInfo>>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let slot_address = |slot| async move {
+        state::storage_at_with_overrides(provider, overrides, token, slot, block).await
+            .map(|word| Some(Address::from_word(word)).filter(|address| !address.is_zero()))
+    };
+    let (kind, implementation, beacon, selector) =
+        if let Some(implementation) = slot_address(EIP1967_IMPLEMENTATION_SLOT).await? {
+            (ProxyKind::Eip1967, implementation, None, None)
+        } else if let Some(beacon) = slot_address(EIP1967_BEACON_SLOT).await? {
+            let input = Bytes::from(IMPLEMENTATION_4BYTE.to_vec());
+            let implementation = call_address(provider, beacon, input, overrides, block).await
+                .ok_or_else(|| eyre::eyre!("Beacon {beacon:?} returned no implementation"))?;
+            (ProxyKind::Beacon, implementation, Some(beacon), None)
+        } else if let Some(implementation) = slot_address(EIP1822_PROXIABLE_SLOT).await? {
+            (ProxyKind::Eip1822, implementation, None, None)
+        } else {
+            let input = [&FACET_ADDRESS_4BYTE[..], B256::right_padding_from(&selector).as_slice()].concat();
+            match call_address(provider, token, input.into(), overrides, block).await {
+                Some(facet) => (ProxyKind::Diamond, facet, None, Some(selector.into())),
+                None => return Ok(None),
+            }
+        };
+    let code = state::code_at_with_overrides(provider, overrides, implementation, block).await?;
+    // Tokens with a catch-all fallback can answer `facetAddress` too
+    if kind == ProxyKind::Diamond && code.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(ProxyInfo {
+        kind,
+        implementation,
+        beacon,
+        selector,
+        implementation_code_hash: alloy_utils::keccak256(code),
+    }))
+}
+
+/// Whether the implementation behind `token` changed since `locator` was found,
+/// in which case a cached locator should be searched for again
+pub async fn is_upgraded<P, T>(
+    provider: &P,
+    token: Address,
+    locator: &StorageLocator,
+) -> Result<bool>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let Some(proxy) = &locator.proxy else {
+        return Ok(false);
+    };
+    let selector = proxy.selector.map(|selector| selector.0).unwrap_or_default();
+    let current = resolve_proxy(
+        provider,
+        token,
+        selector,
+        &StateOverride::default(),
+        BlockNumberOrTag::Latest,
+    ).await?;
+    let code_hash = current.map(|current| current.implementation_code_hash);
+    Ok(code_hash != Some(proxy.implementation_code_hash))
+}
+
+// Reverts and anything but a single non-zero address word are treated as no answer
+async fn call_address<P, T>(
+    provider: &P,
+    to: Address,
+    input: Bytes,
+    overrides: &StateOverride,
+    block: BlockNumberOrTag,
+) -> Option<Address>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let call_request = token::call_req(crate::default_holder(), to, input);
+    let call = provider.call(&call_request).block(block.into());
+    let output = match overrides.is_empty() {
+        true => call.await,
+        false => call.overrides(overrides).await,
+    }.ok()?;
+    if output.len() != 32 || output[..12].iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(Address::from_slice(&output[12..])).filter(|address| !address.is_zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_slots() {
+        let eip1967_slot = |label: &str| {
+            let hash = U256::from_be_bytes(alloy_utils::keccak256(label).0);
+            B256::from(hash - U256::from(1))
+        };
+        assert_eq!(eip1967_slot("eip1967.proxy.implementation"), EIP1967_IMPLEMENTATION_SLOT);
+        assert_eq!(eip1967_slot("eip1967.proxy.beacon"), EIP1967_BEACON_SLOT);
+        assert_eq!(alloy_utils::keccak256("PROXIABLE"), EIP1822_PROXIABLE_SLOT);
+        assert_eq!(alloy_utils::keccak256("implementation()")[..4], IMPLEMENTATION_4BYTE);
+        assert_eq!(alloy_utils::keccak256("facetAddress(bytes4)")[..4], FACET_ADDRESS_4BYTE);
+    }
+
+    #[test]
+    fn test_display_matches_serde() {
+        for kind in [ProxyKind::Eip1967, ProxyKind::Beacon, ProxyKind::Eip1822, ProxyKind::Diamond] {
+            let serialized = serde_json::to_string(&kind).unwrap();
+            assert_eq!(serialized, format!("\"{kind}\""));
+        }
+    }
+
+}
@@ -0,0 +1,170 @@
+use serde::{Serialize, Deserialize};
+use std::cmp::Ordering;
+use crate::common::*;
+
+
+/// Ratio between the balance a getter reports and the value stored for it,
+/// kept as the raw probe it was measured with: the balance reported
+/// (numerator) with the value written to the slot (denominator)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", try_from = "RawUpdateRatio")]
+pub struct UpdateRatio {
+    numerator: U256,
+    denominator: U256,
+}
+
+// Deserialized fields, checked before they become a ratio
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawUpdateRatio {
+    numerator: U256,
+    denominator: U256,
+}
+
+impl TryFrom<RawUpdateRatio> for UpdateRatio {
+    type Error = Error;
+
+    fn try_from(raw: RawUpdateRatio) -> Result<Self> {
+        UpdateRatio::new(raw.numerator, raw.denominator)
+    }
+}
+
+impl UpdateRatio {
+
+    pub const ONE: UpdateRatio = UpdateRatio {
+        numerator: U256::from_limbs([1, 0, 0, 0]),
+        denominator: U256::from_limbs([1, 0, 0, 0]),
+    };
+
+    /// Fails for a zero denominator, as no value was written to measure it
+    pub fn new(numerator: U256, denominator: U256) -> Result<Self> {
+        if denominator.is_zero() {
+            return Err(Error::ZeroRatioDenominator);
+        }
+        Ok(Self { numerator, denominator })
+    }
+
+    pub fn numerator(&self) -> U256 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> U256 {
+        self.denominator
+    }
+
+    pub fn is_one(&self) -> bool {
+        self.numerator == self.denominator
+    }
+
+    /// Balance reported for `stored`, rounded down
+    pub fn reported_for(&self, stored: U256) -> U256 {
+        mul_div(stored, self.numerator, self.denominator)
+    }
+
+    /// Value to store for a reported balance of `reported`, rounded to the
+    /// nearest stored unit (None when the getter doesn't scale with storage)
+    pub fn stored_for(&self, reported: U256) -> Option<U256> {
+        if self.numerator.is_zero() {
+            return None;
+        }
+        let num = U512::from(reported) * U512::from(self.denominator) + U512::from(self.numerator / U256::from(2));
+        let stored = num / U512::from(self.numerator);
+        (stored <= U512::from(U256::MAX)).then(|| stored.to::<U256>())
+    }
+
+    /// Exact ordering by distance from a one-to-one ratio, closest first
+    pub fn cmp_distance_from_one(&self, other: &Self) -> Ordering {
+        let distance = |ratio: &Self| U512::from(ratio.numerator.abs_diff(ratio.denominator));
+        (distance(self) * U512::from(other.denominator))
+            .cmp(&(distance(other) * U512::from(self.denominator)))
+    }
+
+    /// Approximate ratio, for display
+    pub fn to_f64(&self) -> f64 {
+        u256_to_f64(self.numerator) / u256_to_f64(self.denominator)
+    }
+
+}
+
+impl PartialEq for UpdateRatio {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for UpdateRatio {}
+
+impl PartialOrd for UpdateRatio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for UpdateRatio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (U512::from(self.numerator) * U512::from(other.denominator))
+            .cmp(&(U512::from(other.numerator) * U512::from(self.denominator)))
+    }
+}
+
+impl std::fmt::Display for UpdateRatio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_f64())
+    }
+}
+
+fn mul_div(value: U256, numerator: U256, denominator: U256) -> U256 {
+    let result = U512::from(value) * U512::from(numerator) / U512::from(denominator);
+    result.min(U512::from(U256::MAX)).to::<U256>()
+}
+
+// Top 64 significant bits, scaled back by the dropped ones
+fn u256_to_f64(value: U256) -> f64 {
+    let shift = value.bit_len().saturating_sub(64);
+    (value >> shift).to::<u64>() as f64 * 2f64.powi(shift as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(numerator: u128, denominator: u128) -> UpdateRatio {
+        UpdateRatio::new(U256::from(numerator), U256::from(denominator)).unwrap()
+    }
+
+    #[test]
+    fn test_exact_beyond_f64_precision() {
+        let huge = ratio(u128::MAX, 3);
+        assert!(huge.to_f64().is_finite());
+        assert!(huge > ratio(u128::MAX - 1, 3));
+        assert_eq!(ratio(2, 4), ratio(1, 2));
+        assert_eq!(UpdateRatio::new(U256::MAX, U256::MAX).unwrap(), UpdateRatio::ONE);
+    }
+
+    #[test]
+    fn test_inverse_writes() {
+        // eg. shares worth 1.05 of the asset each
+        let shares = ratio(105_000, 100_000);
+        let stored = shares.stored_for(U256::from(21)).unwrap();
+        assert_eq!(stored, U256::from(20));
+        assert_eq!(shares.reported_for(stored), U256::from(21));
+        assert_eq!(ratio(0, 1).stored_for(U256::from(1)), None);
+    }
+
+    #[test]
+    fn test_zero_denominator_is_rejected() {
+        assert!(matches!(UpdateRatio::new(U256::from(1), U256::ZERO), Err(Error::ZeroRatioDenominator)));
+        let json = r#"{"numerator":"0x1","denominator":"0x0"}"#;
+        assert!(serde_json::from_str::<UpdateRatio>(json).is_err());
+        let json = r#"{"numerator":"0x2","denominator":"0x1"}"#;
+        assert_eq!(serde_json::from_str::<UpdateRatio>(json).unwrap(), ratio(2, 1));
+    }
+
+    #[test]
+    fn test_distance_from_one() {
+        let mut ratios = vec![ratio(1, 10), ratio(10, 1), ratio(4, 1), ratio(99, 100)];
+        ratios.sort_by(UpdateRatio::cmp_distance_from_one);
+        assert_eq!(ratios, vec![ratio(99, 100), ratio(1, 10), ratio(4, 1), ratio(10, 1)]);
+    }
+
+}
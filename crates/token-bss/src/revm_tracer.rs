@@ -0,0 +1,286 @@
+use alloy::{
+    primitives::{TxKind, U64},
+    rpc::types::eth::state::StateOverride,
+};
+use revm::{
+    inspector_handle_register,
+    interpreter::{opcode, Interpreter},
+    primitives::{AccountInfo, BlockEnv, Bytecode, EVMError, ExecutionResult, TxEnv},
+    Database, Evm, EvmContext, Inspector,
+};
+use std::{collections::HashMap, future::Future};
+use super::{ops::{state, token}, trace_parser::TraceEvent, tracer::Tracer};
+use crate::common::*;
+
+
+// Each run stops at the first missing account or slot, so this bounds the reruns
+const MAX_STATE_FETCHES: usize = 1024;
+
+/// Runs the call in-process with revm, over state fetched from the provider
+/// (`eth_getCode`, `eth_getStorageAt`, ..) as the call touches it. Works with
+/// any RPC, no debug namespace or local node needed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RevmTracer;
+
+impl Tracer for RevmTracer {
+
+    fn trace_events<P, T>(
+        &self,
+        provider: &P,
+        _contract: Address,
+        call_request: TransactionRequest,
+        block: BlockNumberOrTag,
+        overrides: StateOverride,
+    ) -> impl Future<Output = Result<Vec<TraceEvent>>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async move {
+            let header = provider.get_block(block.into(), false).await?
+                .ok_or_else(|| eyre::eyre!("Block {block:?} not found"))?
+                .header;
+            // Pin the state to the block, as `block` could be a tag
+            let number = header.number.ok_or_else(|| eyre::eyre!("Pending block {block:?}"))?;
+            let block_env = BlockEnv {
+                number: U256::from(number),
+                coinbase: header.miner,
+                timestamp: U256::from(header.timestamp),
+                gas_limit: U256::from(header.gas_limit),
+                basefee: U256::ZERO,
+                difficulty: header.difficulty,
+                prevrandao: header.mix_hash,
+                ..BlockEnv::default()
+            };
+            let tx_env = tx_env(&call_request)?;
+
+            let mut db = ForkDb::with_overrides(overrides);
+            for _ in 0..MAX_STATE_FETCHES {
+                match run(&mut db, &block_env, &tx_env) {
+                    Ok(events) => return events,
+                    Err(EVMError::Database(missing)) => {
+                        db.fetch(provider, missing, BlockNumberOrTag::Number(number)).await?
+                    },
+                    Err(err) => return Err(Error::TraceCallFailed(format!("{err:?}"))),
+                }
+            }
+            Err(Error::TraceCallFailed("Call touches too much state".to_string()))
+        }
+    }
+
+}
+
+fn tx_env(call_request: &TransactionRequest) -> Result<TxEnv> {
+    let to = match call_request.to() {
+        Some(TxKind::Call(to)) => to,
+        _ => return Err(eyre::eyre!("Only calls can be traced").into()),
+    };
+    Ok(TxEnv {
+        caller: call_request.from().unwrap_or_default(),
+        gas_limit: call_request.gas_limit().unwrap_or(token::CALL_GAS_LIMIT) as u64,
+        // No fees, so the caller doesn't need a balance
+        gas_price: U256::ZERO,
+        transact_to: TxKind::Call(to),
+        value: call_request.value().unwrap_or_default(),
+        data: call_request.input().cloned().unwrap_or_default(),
+        ..TxEnv::default()
+    })
+}
+
+fn run(
+    db: &mut ForkDb,
+    block_env: &BlockEnv,
+    tx_env: &TxEnv,
+) -> std::result::Result<Result<Vec<TraceEvent>>, EVMError<MissingState>> {
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .with_external_context(EventInspector::default())
+        .modify_block_env(|env| *env = block_env.clone())
+        .modify_tx_env(|env| *env = tx_env.clone())
+        .append_handler_register(inspector_handle_register)
+        .build();
+    let result = evm.transact()?.result;
+    let events = std::mem::take(&mut evm.context.external.events);
+    Ok(match result {
+        ExecutionResult::Success { .. } => Ok(events),
+        ExecutionResult::Revert { .. } => Err(Error::TraceCallFailed("call reverted".to_string())),
+        ExecutionResult::Halt { reason, .. } => Err(Error::TraceCallFailed(format!("call halted: {reason:?}"))),
+    })
+}
+
+/// Collects SLOADs and keccak preimages as the interpreter steps through them
+#[derive(Default)]
+struct EventInspector {
+    events: Vec<TraceEvent>,
+    pending_sload: Option<(Address, B256)>,
+}
+
+impl<DB: Database> Inspector<DB> for EventInspector {
+
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        match interp.current_opcode() {
+            opcode::SLOAD => {
+                if let Ok(slot) = interp.stack.peek(0) {
+                    // Storage context, ie. the proxy for delegatecalls
+                    self.pending_sload = Some((interp.contract.address, slot.into()));
+                }
+            },
+            opcode::KECCAK256 => {
+                let (Ok(offset), Ok(len)) = (interp.stack.peek(0), interp.stack.peek(1)) else {
+                    return;
+                };
+                let (Ok(offset), Ok(len)) = (usize::try_from(offset), usize::try_from(len)) else {
+                    return;
+                };
+                let preimage = offset.checked_add(len)
+                    .and_then(|end| interp.shared_memory.context_memory().get(offset..end));
+                if let Some(preimage) = preimage {
+                    self.events.push(TraceEvent::Keccak(preimage.to_vec()));
+                }
+            },
+            _ => (),
+        }
+    }
+
+    // The loaded value is on top of the stack once SLOAD is done
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        if let Some((contract, slot)) = self.pending_sload.take() {
+            let value = interp.stack.peek(0).unwrap_or_default();
+            self.events.push(TraceEvent::Sload { contract, slot, value: value.into() });
+        }
+    }
+
+}
+
+/// State the call needed, but was not fetched yet
+#[derive(Debug, Clone, Copy)]
+enum MissingState {
+    Account(Address),
+    Storage(Address, U256),
+    BlockHash(U256),
+}
+
+/// Database that fails on anything not fetched yet, so the call can be
+/// rerun once the missing state has been fetched asynchronously
+#[derive(Default)]
+struct ForkDb {
+    accounts: HashMap<Address, AccountInfo>,
+    storage: HashMap<(Address, U256), U256>,
+    block_hashes: HashMap<U256, B256>,
+    overrides: StateOverride,
+}
+
+impl ForkDb {
+
+    fn with_overrides(overrides: StateOverride) -> Self {
+        Self { overrides, ..Self::default() }
+    }
+
+    async fn fetch<P, T>(
+        &mut self,
+        provider: &P,
+        missing: MissingState,
+        block: BlockNumberOrTag,
+    ) -> Result<()>
+        where P: Provider<T>, T: Transport + Clone
+    {
+        match missing {
+            MissingState::Account(address) => {
+                let client = provider.client();
+                let (balance, nonce, code) = tokio::try_join!(
+                    client.request::<_, U256>("eth_getBalance", (address, block)),
+                    client.request::<_, U64>("eth_getTransactionCount", (address, block)),
+                    client.request::<_, Bytes>("eth_getCode", (address, block)),
+                )?;
+                let mut info = AccountInfo::new(balance, nonce.to(), B256::ZERO, Bytecode::new_raw(code));
+                if let Some(account) = self.overrides.get(&address) {
+                    info.balance = account.balance.unwrap_or(info.balance);
+                    info.nonce = account.nonce.map(|nonce| nonce.to()).unwrap_or(info.nonce);
+                    if let Some(code) = account.code.clone() {
+                        info.code = Some(Bytecode::new_raw(code));
+                    }
+                }
+                info.code_hash = info.code.as_ref().map(Bytecode::hash_slow).unwrap_or_default();
+                self.accounts.insert(address, info);
+            },
+            MissingState::Storage(address, index) => {
+                let value = state::storage_at(provider, address, index.into(), block).await?;
+                self.storage.insert((address, index), value.into());
+            },
+            MissingState::BlockHash(number) => {
+                let block = BlockNumberOrTag::Number(number.to());
+                let hash = provider.get_block(block.into(), false).await?
+                    .and_then(|block| block.header.hash)
+                    .unwrap_or_default();
+                self.block_hashes.insert(number, hash);
+            },
+        }
+        Ok(())
+    }
+
+}
+
+impl Database for ForkDb {
+    type Error = MissingState;
+
+    fn basic(&mut self, address: Address) -> std::result::Result<Option<AccountInfo>, MissingState> {
+        self.accounts.get(&address)
+            .cloned()
+            .map(Some)
+            .ok_or(MissingState::Account(address))
+    }
+
+    // Code is fetched together with its account
+    fn code_by_hash(&mut self, code_hash: B256) -> std::result::Result<Bytecode, MissingState> {
+        let code = self.accounts.values()
+            .find(|info| info.code_hash == code_hash)
+            .and_then(|info| info.code.clone())
+            .unwrap_or_default();
+        Ok(code)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> std::result::Result<U256, MissingState> {
+        if let Some(value) = state::overridden_storage(&self.overrides, address, index.into()) {
+            return Ok(value.into());
+        }
+        self.storage.get(&(address, index))
+            .copied()
+            .ok_or(MissingState::Storage(address, index))
+    }
+
+    fn block_hash(&mut self, number: U256) -> std::result::Result<B256, MissingState> {
+        self.block_hashes.get(&number)
+            .copied()
+            .ok_or(MissingState::BlockHash(number))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{node_bindings::Anvil, providers::ReqwestProvider};
+    use crate::{trace_parser::TraceParser, lang::EvmLanguage, getter::Getter, test_utils::rpc_endpoint};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_revm_events_eth_usdc() -> eyre::Result<()> {
+        let anvil = Anvil::new().fork(rpc_endpoint()?).spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let token: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse()?;
+        let holder = Address::with_last_byte(1);
+        let getter = Getter::balance_of(holder);
+        let call_request = token::call_req(holder, token, getter.input());
+
+        let events = RevmTracer.trace_events(
+            &provider, 
+            token, 
+            call_request, 
+            BlockNumberOrTag::Latest, 
+            StateOverride::default(),
+        ).await?;
+        let result = TraceParser::parse_events(events, &getter.keys)?;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].slot, B256::from(U256::from(9)));
+        assert_eq!(result[0].lang, EvmLanguage::Solidity);
+        Ok(())
+    }
+
+}
@@ -0,0 +1,264 @@
+use super::{
+    locator::{StorageLocator, StorageWrite},
+    getter::Getter,
+    balance_setter::BalanceSetter,
+    writer::{StorageWriter, AnvilStorageWriter},
+    ops::{state, token},
+};
+use alloy::rpc::types::eth::state::StateOverride;
+use crate::common::*;
+
+
+// Secant / bisection steps before settling for the closest stored value
+const MAX_SOLVER_STEPS: usize = 256;
+
+/// Outcome of setting a getter to an exact target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExactWrite {
+    pub target: U256,
+    /// Value written to storage
+    pub stored: U256,
+    /// Value the contract reports after the write
+    pub value: U256,
+}
+
+impl ExactWrite {
+
+    pub fn is_exact(&self) -> bool {
+        self.value == self.target
+    }
+
+    /// Distance of the reported value from the target
+    pub fn deviation(&self) -> U256 {
+        self.value.abs_diff(self.target)
+    }
+
+}
+
+/// Write `value` into the storage word backing `getter` on an Anvil node,
+/// returning the value the contract reports afterwards. Values keyed by 
+/// an id (indirect locators) can only be written for keys with an id.
+pub async fn set_getter_value<P, T>(
+    provider: &P,
+    contract: Address,
+    getter: &Getter,
+    value: U256,
+    locator: &StorageLocator,
+) -> Result<U256>
+    where P: Provider<T>, T: Transport + Clone
+{
+    write_getter_value(provider, &AnvilStorageWriter, contract, getter, value, locator).await
+}
+
+/// Same as [`set_getter_value`], with the storage written by `writer`
+pub async fn write_getter_value<P, T>(
+    provider: &P,
+    writer: &impl StorageWriter,
+    contract: Address,
+    getter: &Getter,
+    value: U256,
+    locator: &StorageLocator,
+) -> Result<U256>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let block = BlockNumberOrTag::Latest;
+    let keys = state::resolve_keys(
+        provider, 
+        locator, 
+        &locator.mapping_keys(&getter.args), 
+        &StateOverride::default(), 
+        block,
+    ).await?;
+    let key = locator.storage_key_for(&keys);
+    let current = state::storage_at(provider, locator.contract, key, block).await?;
+    let write = locator.storage_write_for(&keys, value, current)?;
+    writer.set_storage_at(provider, write.contract, write.key, write.value).await?;
+
+    let call_request = token::call_req(crate::default_holder(), contract, getter.input());
+    token::call_request(provider, &call_request, block).await
+}
+
+/// Storage word currently backing `getter`, as the write that puts it back
+pub(crate) async fn current_word<P, T>(
+    provider: &P,
+    getter: &Getter,
+    locator: &StorageLocator,
+) -> Result<StorageWrite>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let block = BlockNumberOrTag::Latest;
+    let keys = state::resolve_keys(
+        provider, 
+        locator, 
+        &locator.mapping_keys(&getter.args), 
+        &StateOverride::default(), 
+        block,
+    ).await?;
+    let key = locator.storage_key_for(&keys);
+    let value = state::storage_at(provider, locator.contract, key, block).await?;
+    Ok(StorageWrite { contract: locator.contract, key, value })
+}
+
+/// Same as [`set_getter_value`], but `target` is the value the getter should
+/// report rather than the one stored, eg. the balance of a rebasing or 
+/// share-based token. The stored value is solved for with state-override calls.
+pub async fn set_getter_value_exact<P, T>(
+    provider: &P,
+    contract: Address,
+    getter: &Getter,
+    target: U256,
+    locator: &StorageLocator,
+) -> Result<ExactWrite>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let stored = solve_stored_value(provider, contract, getter, target, locator).await?;
+    let value = set_getter_value(provider, contract, getter, stored, locator).await?;
+    Ok(ExactWrite { target, stored, value })
+}
+
+/// Set the ERC20 balance of `holder` on an Anvil node so that `balanceOf` 
+/// reports `target`. See [`BalanceSetter`] for other nodes.
+pub async fn set_balance_exact<P, T>(
+    provider: &P,
+    token: Address,
+    holder: Address,
+    target: U256,
+    locator: Option<StorageLocator>,
+) -> Result<ExactWrite>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let setter = BalanceSetter::new(provider, token);
+    let setter = match locator {
+        Some(locator) => setter.with_locator(locator),
+        None => setter,
+    };
+    setter.set(holder, target).await.map(|write| write.balance)
+}
+
+/// Stored value for which `getter` reports `target`, or the closest one if no
+/// stored value maps onto it exactly. The measured update ratio gives the first 
+/// guess, then the target is bracketed and narrowed down with secant steps, 
+/// falling back to bisection when they stop halving the bracket. Reported 
+/// values are assumed not to decrease with the stored value.
+pub async fn solve_stored_value<P, T>(
+    provider: &P,
+    contract: Address,
+    getter: &Getter,
+    target: U256,
+    locator: &StorageLocator,
+) -> Result<U256>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let block = BlockNumberOrTag::Latest;
+    let keys = state::resolve_keys(
+        provider, 
+        locator, 
+        &locator.mapping_keys(&getter.args), 
+        &StateOverride::default(), 
+        block,
+    ).await?;
+    let key = locator.storage_key_for(&keys);
+    let current = state::storage_at(provider, locator.contract, key, block).await?;
+    let call_request = token::call_req(crate::default_holder(), contract, getter.input());
+    let reported_for = |stored: U256| {
+        let keys = &keys;
+        let call_request = &call_request;
+        async move {
+            let write = locator.storage_write_for(keys, stored, current)?;
+            token::call_request_with_storage_overrides(
+                provider,
+                call_request,
+                &StateOverride::default(),
+                write.contract,
+                write.key,
+                write.value,
+                block,
+            ).await
+        }
+    };
+
+    let max = locator.max_value();
+    let guess = locator.update_ratio.stored_for(target).unwrap_or(target).min(max);
+    let at_guess = reported_for(guess).await?;
+    if at_guess == target {
+        return Ok(guess);
+    }
+    // Bracket the target, doubling the step away from the guess
+    let mut step = guess.max(U256::from(1));
+    let ((mut lo, mut lo_val), (mut hi, mut hi_val)) = if at_guess < target {
+        let (mut lo, mut lo_val) = (guess, at_guess);
+        loop {
+            let hi = lo.saturating_add(step).min(max);
+            let hi_val = reported_for(hi).await?;
+            if hi_val >= target || hi == max {
+                break ((lo, lo_val), (hi, hi_val));
+            }
+            (lo, lo_val, step) = (hi, hi_val, step.saturating_mul(U256::from(2)));
+        }
+    } else {
+        let (mut hi, mut hi_val) = (guess, at_guess);
+        loop {
+            let lo = hi.saturating_sub(step);
+            let lo_val = reported_for(lo).await?;
+            if lo_val <= target || lo.is_zero() {
+                break ((lo, lo_val), (hi, hi_val));
+            }
+            (hi, hi_val, step) = (lo, lo_val, step.saturating_mul(U256::from(2)));
+        }
+    };
+
+    let mut bisect = false;
+    for _ in 0..MAX_SOLVER_STEPS {
+        if hi - lo <= U256::from(1) || lo_val >= target || hi_val <= target {
+            break;
+        }
+        let width = hi - lo;
+        let next = match bisect || hi_val <= lo_val {
+            true => lo + width / U256::from(2),
+            false => {
+                let offset = U512::from(target - lo_val) * U512::from(width) / U512::from(hi_val - lo_val);
+                lo + offset.to::<U256>()
+            }
+        };
+        let next = if next <= lo || next >= hi { lo + width / U256::from(2) } else { next };
+        let next_val = reported_for(next).await?;
+        if next_val == target {
+            return Ok(next);
+        }
+        if next_val < target {
+            (lo, lo_val) = (next, next_val);
+        } else {
+            (hi, hi_val) = (next, next_val);
+        }
+        bisect = hi - lo > width / U256::from(2);
+    }
+    Ok(match lo_val.abs_diff(target) <= hi_val.abs_diff(target) {
+        true => lo,
+        false => hi,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{node_bindings::Anvil, providers::ReqwestProvider};
+    use crate::test_utils::rpc_endpoint;
+    use super::*;
+    use eyre::Result;
+
+    #[tokio::test]
+    async fn test_exact_balance_eth_stlink() -> Result<()> {
+        let anvil = Anvil::new().fork(rpc_endpoint()?).spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let token: Address = "0xb8b295df2cd735b15BE5Eb419517Aa626fc43cD5".parse()?;
+        let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse()?;
+        let target = U256::from(1_234_567_890_123_456_789_u128);
+
+        let write = set_balance_exact(&provider, token, holder, target, None).await?;
+
+        // Shares are worth more than one stLINK each, so the stored value is lower
+        assert!(write.stored < target);
+        assert!(write.deviation() <= U256::from(1));
+        Ok(())
+    }
+
+}
@@ -1,105 +1,624 @@
+use alloy::rpc::types::eth::state::{StateOverride, AccountOverride};
 use futures::future::join_all;
+use std::{collections::HashMap, marker::PhantomData, time::Duration};
 use super::{
     trace_parser::TraceParser, 
-    ops::{token, trace}, 
-    lang::EvmLanguage, 
+    access_list_parser::AccessListParser,
+    strategy::Strategy,
+    fingerprint,
+    proxy::{self, ProxyInfo},
+    capabilities::Capabilities,
+    tracer::{Tracer, RpcTracer},
+    ops::{token, state, access_list}, 
+    locator::{StorageLocator, WORD_BITS},
+    lang::EvmLanguage,
+    getter::Getter,
+    verification::{self, Sample, Verification},
     utils,
 };
 use crate::common::*;
 
-// todo: make a builder here
-// todo: use a struct
-type SlotOutput = (Address, B256, f64, String);
 
-pub async fn find_balance_slots_and_update_ratio<'a, P, T>(
+// Holders probed on top of the traced one, for getters keyed by the holder
+const EXTRA_VERIFICATION_HOLDERS: usize = 2;
+
+/// Outcome of a slot search
+#[derive(Debug, Clone)]
+pub struct SlotSearch {
+    /// Most confident candidate, closest to a one-to-one update ratio
+    pub best: StorageLocator,
+    /// All verified candidates, ranked from best to worst
+    pub candidates: Vec<StorageLocator>,
+}
+
+/// Builder for a balance (or any other getter) slot search
+pub struct SlotFinder<'a, P, T, Tr = RpcTracer> {
+    provider: &'a P,
+    token: Address,
+    holder: Address,
+    block: Option<BlockNumberOrTag>,
+    gas_limit: u128,
+    verification_value: Option<U256>,
+    candidate_limit: Option<usize>,
+    timeout: Option<Duration>,
+    tracer: Tr,
+    getter: Option<Getter>,
+    strategies: Option<Vec<Strategy>>,
+    capabilities: Option<Capabilities>,
+    poke_fallback: bool,
+    overrides: StateOverride,
+    _phantom_transport: PhantomData<T>,
+}
+
+impl<'a, P, T> SlotFinder<'a, P, T> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    pub fn new(provider: &'a P, token: Address) -> Self {
+        Self {
+            provider,
+            token,
+            holder: crate::default_holder(),
+            block: None,
+            gas_limit: token::CALL_GAS_LIMIT,
+            verification_value: None,
+            candidate_limit: None,
+            timeout: None,
+            tracer: RpcTracer,
+            getter: None,
+            strategies: None,
+            capabilities: None,
+            poke_fallback: true,
+            overrides: StateOverride::default(),
+            _phantom_transport: PhantomData,
+        }
+    }
+
+}
+
+impl<'a, P, T, Tr> SlotFinder<'a, P, T, Tr> 
+    where P: Provider<T>, T: Transport + Clone, Tr: Tracer
+{
+
+    /// Holder whose `balanceOf` call is traced (default: `0x…01`)
+    pub fn with_holder(mut self, holder: Address) -> Self {
+        self.holder = holder;
+        self
+    }
+
+    /// Block to trace and verify at (default: latest)
+    pub fn with_block(mut self, block: BlockNumberOrTag) -> Self {
+        self.block = Some(block);
+        self
+    }
+
+    /// Gas limit for the traced and verification calls
+    pub fn with_gas_limit(mut self, gas_limit: u128) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    /// Reference value written to a candidate slot during verification 
+    /// (default: random), probed next to zero, a small value and one close 
+    /// to the maximum of the balance field
+    pub fn with_verification_value(mut self, value: U256) -> Self {
+        self.verification_value = Some(value);
+        self
+    }
+
+    /// Max number of trace candidates to verify
+    pub fn with_candidate_limit(mut self, limit: usize) -> Self {
+        self.candidate_limit = Some(limit);
+        self
+    }
+
+    /// Abort the search if it takes longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Trace with `tracer` instead of the provider's `debug_traceCall`
+    pub fn with_tracer<Tr2: Tracer>(self, tracer: Tr2) -> SlotFinder<'a, P, T, Tr2> {
+        SlotFinder {
+            provider: self.provider,
+            token: self.token,
+            holder: self.holder,
+            block: self.block,
+            gas_limit: self.gas_limit,
+            verification_value: self.verification_value,
+            candidate_limit: self.candidate_limit,
+            timeout: self.timeout,
+            tracer,
+            getter: self.getter,
+            strategies: self.strategies,
+            capabilities: self.capabilities,
+            poke_fallback: self.poke_fallback,
+            overrides: self.overrides,
+            _phantom_transport: PhantomData,
+        }
+    }
+
+    /// Search for the slot backing `getter` instead of `balanceOf(holder)`, 
+    /// the holder is then only used as the caller
+    pub fn with_getter(mut self, getter: Getter) -> Self {
+        self.getter = Some(getter);
+        self
+    }
+
+    /// Only discover candidates with `strategy`, skipping capability probing
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategies = Some(vec![strategy]);
+        self
+    }
+
+    /// Try `strategies` in order until one yields a verified slot, skipping 
+    /// capability probing (default: supported ones of `Strategy::DEFAULT_ORDER`)
+    pub fn with_strategies(mut self, strategies: Vec<Strategy>) -> Self {
+        self.strategies = Some(strategies);
+        self
+    }
+
+    /// Reuse `capabilities` probed earlier for the same provider
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// Whether the default chain ends by poking every SLOADed slot (default: true). 
+    /// Such locations are only valid for the traced holder.
+    pub fn with_poke_fallback(mut self, poke_fallback: bool) -> Self {
+        self.poke_fallback = poke_fallback;
+        self
+    }
+
+    /// Trace and verify with `overrides` applied, eg. to search a token that 
+    /// is not deployed yet. Access lists can't take overrides, so that 
+    /// strategy is skipped.
+    pub fn with_state_overrides(mut self, overrides: StateOverride) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Run `code` as the runtime code of `address` (the token, or the 
+    /// implementation behind its proxy)
+    pub fn with_code(mut self, address: Address, code: Bytes) -> Self {
+        self.account_override(address).code = Some(code);
+        self
+    }
+
+    /// Set storage slots of `address` on top of its current storage
+    pub fn with_storage(mut self, address: Address, storage: HashMap<B256, B256>) -> Self {
+        self.account_override(address).state_diff
+            .get_or_insert_with(HashMap::new)
+            .extend(storage);
+        self
+    }
+
+    fn account_override(&mut self, address: Address) -> &mut AccountOverride {
+        self.overrides.entry(address).or_default()
+    }
+
+    pub async fn find(self) -> Result<SlotSearch> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.search()).await
+                .map_err(|_| Error::Timeout)?,
+            None => self.search().await,
+        }
+    }
+
+    async fn search(self) -> Result<SlotSearch> {
+        let Self { 
+            provider, 
+            token, 
+            holder, 
+            block, 
+            gas_limit, 
+            verification_value, 
+            candidate_limit, 
+            tracer, 
+            getter,
+            strategies,
+            capabilities,
+            poke_fallback,
+            overrides,
+            .. 
+        } = self;
+        let block = resolve_block_number(provider, block).await?;
+        let getter = getter.unwrap_or_else(|| Getter::balance_of(holder));
+        let overrides_set = !overrides.is_empty();
+        let ctx = SearchCtx { token, holder, getter, block, gas_limit, verification_value, overrides };
+        let mut strategies = match strategies {
+            Some(strategies) => strategies,
+            None => {
+                let capabilities = match capabilities {
+                    Some(capabilities) => capabilities,
+                    None => Capabilities::probe(provider).await,
+                };
+                supported_strategies(&capabilities, !tracer.is_rpc(), poke_fallback)
+            }
+        };
+        if overrides_set {
+            strategies.retain(|strategy| *strategy != Strategy::AccessList);
+        }
+
+        let mut search_err = None;
+        for strategy in strategies {
+            let result = search_with(
+                provider, 
+                &ctx, 
+                strategy, 
+                &tracer, 
+                candidate_limit,
+            ).await;
+            match result {
+                Ok(mut candidates) => {
+                    let proxy = resolve_balance_proxy(provider, &ctx).await;
+                    for candidate in candidates.iter_mut() {
+                        candidate.proxy = proxy.clone();
+                    }
+                    let best = candidates[0].clone();
+                    return Ok(SlotSearch { best, candidates })
+                }
+                Err(err) => search_err = Some(more_severe(search_err, err)),
+            }
+        }
+        Err(search_err.unwrap_or(Error::NoSupportedStrategy))
+    }
+
+}
+
+/// Parameters shared by the calls made during a single search
+#[derive(Debug, Clone)]
+struct SearchCtx {
+    token: Address,
+    holder: Address,
+    getter: Getter,
+    block: u64,
+    gas_limit: u128,
+    verification_value: Option<U256>,
+    overrides: StateOverride,
+}
+
+impl SearchCtx {
+
+    fn call_req(&self) -> TransactionRequest {
+        token::call_req(self.holder, self.token, self.getter.input())
+            .with_gas_limit(self.gas_limit)
+    }
+
+    /// Same search for another holder, if the getter is keyed by the holder
+    fn for_holder(&self, holder: Address) -> Option<SearchCtx> {
+        let word = self.holder.into_word();
+        if !self.getter.args.contains(&word) {
+            return None;
+        }
+        let replace = |words: &[B256]| words.iter()
+            .map(|w| if *w == word { holder.into_word() } else { *w })
+            .collect();
+        let mut ctx = self.clone();
+        ctx.holder = holder;
+        ctx.getter.args = replace(&self.getter.args);
+        ctx.getter.keys = replace(&self.getter.keys);
+        Some(ctx)
+    }
+
+}
+
+pub async fn find_balance_slots_and_update_ratio<P, T>(
     provider: &P,
     holder: Address, 
     token: Address,
-    trace_fn: Option<TraceFn>,
-) -> Result<SlotOutput> 
+    tracer: impl Tracer,
+) -> Result<StorageLocator> 
     where P: Provider<T>, T: Transport + Clone
 {
-    let slots = find_balance_slots(provider, holder, token, trace_fn).await?;
-    closest_slot(provider, token, holder, slots).await
+    crate::find_slot(provider, token, Some(holder), tracer).await
 }
 
-pub async fn find_balance_slots<'a, P, T, N>(
+async fn resolve_block_number<P, T>(
     provider: &P,
-    holder: Address,
-    token: Address,
-    trace_fn: Option<TraceFn>,
-) -> Result<Vec<(Address, B256, EvmLanguage)>> 
-    where P: Provider<T, N>, T: Transport + Clone, N: Network
+    block: Option<BlockNumberOrTag>,
+) -> Result<u64> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    match block {
+        Some(BlockNumberOrTag::Number(number)) => Ok(number),
+        None | Some(BlockNumberOrTag::Latest) => Ok(provider.get_block_number().await?),
+        Some(tag) => provider.get_block(tag.into(), false).await?
+            .and_then(|block| block.header.number)
+            .ok_or_else(|| eyre::eyre!("Block {tag:?} not found").into()),
+    }
+}
+
+fn supported_strategies(
+    capabilities: &Capabilities, 
+    local_trace: bool, 
+    poke_fallback: bool,
+) -> Vec<Strategy> {
+    Strategy::DEFAULT_ORDER.into_iter()
+        .filter(|strategy| poke_fallback || *strategy != Strategy::Poke)
+        .filter(|strategy| strategy.is_supported(capabilities, local_trace))
+        .collect()
+}
+
+// Proxy info is only kept with balance locators, to tell when a cached one is
+// stale. It's informational, so a failed lookup doesn't discard a verified slot.
+async fn resolve_balance_proxy<P, T>(provider: &P, ctx: &SearchCtx) -> Option<ProxyInfo> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    if ctx.getter != Getter::balance_of(ctx.holder) {
+        return None;
+    }
+    proxy::resolve_proxy(
+        provider, 
+        ctx.token, 
+        ctx.getter.selector, 
+        &ctx.overrides, 
+        ctx.block.into(),
+    ).await.ok().flatten()
+}
+
+// Failures (eg. a transport error) are kept over not-found errors, so a slot is
+// only reported missing when every strategy ran cleanly
+fn more_severe(current: Option<Error>, err: Error) -> Error {
+    match current {
+        Some(current) if !current.is_not_found() => current,
+        _ => err,
+    }
+}
+
+/// Verified candidates found with `strategy`, ranked from best to worst
+async fn search_with<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+    strategy: Strategy,
+    tracer: &impl Tracer,
+    candidate_limit: Option<usize>,
+) -> Result<Vec<StorageLocator>> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let mut slots = match strategy {
+        Strategy::Fingerprint => find_fingerprint_slots(provider, ctx).await?,
+        Strategy::StructLogs => find_balance_slots(provider, ctx, tracer).await?,
+        Strategy::AccessList => find_access_list_slots(provider, ctx).await?,
+        Strategy::Poke => find_sloaded_slots(provider, ctx, tracer).await?,
+    };
+    if let Some(limit) = candidate_limit {
+        slots.truncate(limit);
+    }
+    for slot in slots.iter_mut() {
+        slot.strategy = strategy;
+    }
+    let candidates = ranked_slots(provider, ctx, slots).await?;
+    if candidates.is_empty() {
+        return Err(Error::VerificationFailed);
+    }
+    Ok(candidates)
+}
+
+async fn find_balance_slots<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+    tracer: impl Tracer,
+) -> Result<Vec<StorageLocator>> 
+    where P: Provider<T>, T: Transport + Clone
 {
-    let tx_request = token::balanceof_call_req(holder, token)?;
-    let response = trace::default_trace_call(provider, tx_request, None, trace_fn).await?;
-    let matches = TraceParser::parse(response.struct_logs, token, holder)?;
+    let events = tracer.trace_events(
+        provider, 
+        ctx.token,
+        ctx.call_req(), 
+        ctx.block.into(), 
+        ctx.overrides.clone(),
+    ).await?;
+    let matches = TraceParser::parse_events(events, &ctx.getter.keys)?;
     Ok(matches)
 }
 
-// Note this would choose 0 over 2
-async fn closest_slot<P, T>(
+async fn find_sloaded_slots<P, T>(
     provider: &P,
-    token: Address,
-    holder: Address,
-    slots: Vec<(Address, B256, EvmLanguage)>
-) -> Result<SlotOutput, eyre::Error> 
+    ctx: &SearchCtx,
+    tracer: impl Tracer,
+) -> Result<Vec<StorageLocator>> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let events = tracer.trace_events(
+        provider, 
+        ctx.token,
+        ctx.call_req(), 
+        ctx.block.into(), 
+        ctx.overrides.clone(),
+    ).await?;
+    TraceParser::parse_event_sloads(events, &ctx.getter.keys)
+}
+
+// Layouts are only known for balances
+async fn find_fingerprint_slots<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+) -> Result<Vec<StorageLocator>> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    if ctx.getter != Getter::balance_of(ctx.holder) {
+        return Err(Error::UnknownTokenFamily);
+    }
+    let family = fingerprint::fingerprint_token(provider, ctx.token, &ctx.overrides, ctx.block.into()).await?
+        .ok_or(Error::UnknownTokenFamily)?;
+    Ok(family.predicted_locators(ctx.token))
+}
+
+async fn find_access_list_slots<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+) -> Result<Vec<StorageLocator>> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let access_list = access_list::create_access_list(provider, &ctx.call_req(), ctx.block.into()).await?;
+    AccessListParser::parse(&access_list, &ctx.getter.keys)
+}
+
+async fn ranked_slots<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+    slots: Vec<StorageLocator>,
+) -> Result<Vec<StorageLocator>> 
     where P: Provider<T>, T: Transport + Clone
 {
-    let d_one = |x: f64| ((x - 1.0).abs() * 100.) as u8;
     let future_results = join_all(slots.into_iter()
-        .map(|(c, s, la)| async move {
-            slot_update_to_bal_ratio(provider, token, c,s, holder, la)
-                .await
-                .map(|ur| (c, s, ur, la.to_string()))
-        })
+        .map(|candidate| probe_slot(provider, ctx, candidate))
     );
-    future_results.await
+    let mut locators = future_results.await
         .into_iter()
         .filter_map(|x| x.ok())
-        .min_by_key(|x| d_one(x.2))
-        .ok_or_else(|| eyre::eyre!("No valid slots found"))
+        .collect::<Vec<_>>();
+    locators.sort_by(verification::compare_candidates);
+    Ok(locators)
 }
 
-// todo: too many params
-// todo: more suiting name
-async fn slot_update_to_bal_ratio<P, T>(
-    provider: &P, 
-    token: Address,
-    storage_contract: Address,
-    slot: B256,
-    holder: Address,
-    lang: EvmLanguage,
-) -> Result<f64> 
+/// Fill in the balance field, update ratio and discovery context of a candidate
+async fn probe_slot<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+    mut locator: StorageLocator,
+) -> Result<StorageLocator> 
     where P: Provider<T>, T: Transport + Clone
 {
-    let new_slot_val = U256::from(rand::random::<u128>()); // todo: In scenario where this is excatly the same as the current balance it fails
-    let map_loc = lang.mapping_loc(slot, holder);
-    let call_request = token::balanceof_call_req(holder, token)?;
+    let key = storage_key(provider, ctx, &locator).await?;
+    (locator.offset, locator.width) = balance_bit_range(provider, ctx, locator.contract, key).await;
+    let Verification { update_ratio, confidence } = verify_slot(provider, ctx, &locator).await?;
+    (locator.update_ratio, locator.confidence) = (update_ratio, confidence);
+    locator.code_hash = state::code_hash_with_overrides(
+        provider, 
+        &ctx.overrides, 
+        locator.contract, 
+        ctx.block.into(),
+    ).await?;
+    locator.block = ctx.block;
+    locator.key_order = ctx.getter.key_order();
+    Ok(locator)
+}
 
-    let override_bal_future = token::call_request_with_storage_overrides(
+// Indirect locators need the id of the holder to derive the key
+async fn storage_key<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+    locator: &StorageLocator,
+) -> Result<B256> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let keys = state::resolve_keys(
+        provider, 
+        locator, 
+        &ctx.getter.keys, 
+        &ctx.overrides, 
+        ctx.block.into(),
+    ).await?;
+    Ok(locator.storage_key_for(&keys))
+}
+
+// Balance could share the storage word with other fields (eg. uint96 in COMP), 
+// so the word is overridden with a pattern of distinct bytes to see which part 
+// of it is returned. Anything that is not a contiguous byte range counts as full word.
+async fn balance_bit_range<P, T>(
+    provider: &P,
+    ctx: &SearchCtx,
+    storage_contract: Address,
+    key: B256,
+) -> (u16, u16) 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let pattern = utils::byte_index_pattern();
+    token::call_request_with_storage_overrides(
         provider,
-        &call_request,
+        &ctx.call_req(),
+        &ctx.overrides,
         storage_contract,
-        map_loc,
-        new_slot_val.into(),
+        key,
+        pattern.into(),
+        ctx.block.into(),
+    ).await
+        .ok()
+        .and_then(utils::bit_range_from_probe)
+        .unwrap_or((0, WORD_BITS))
+}
+
+// Poked slots could be anything the getter depends on (eg. a total used for 
+// shares), and a slot matching one holder could still be the wrong mapping, so 
+// the balance has to scale the same way for several values and holders. Raw and 
+// indirect locations are only probed for the traced holder, as others have no 
+// key (or id) to write to.
+async fn verify_slot<P, T>(
+    provider: &P, 
+    ctx: &SearchCtx,
+    locator: &StorageLocator,
+) -> Result<Verification> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let values = verification::probe_values(ctx.verification_value, locator.max_value());
+    let other_ctxs: Vec<_> = match locator.lang == EvmLanguage::Raw || locator.indirect.is_some() {
+        true => Vec::new(),
+        false => (0..EXTRA_VERIFICATION_HOLDERS)
+            .filter_map(|_| ctx.for_holder(Address::from(rand::random::<[u8; 20]>())))
+            .collect(),
+    };
+    let holder_samples = join_all(values.iter()
+        .map(|value| sample_balance(provider, ctx, locator, *value))
     );
-    let real_bal_future = token::call_request(provider, &call_request);
-    let (override_bal, real_bal) = tokio::try_join!(override_bal_future, real_bal_future)?;
+    let other_samples = join_all(other_ctxs.iter()
+        .flat_map(|other| values.iter().map(move |value| (other, *value)))
+        .map(|(other, value)| sample_balance(provider, other, locator, value))
+    );
+    let (holder_samples, other_samples) = tokio::join!(holder_samples, other_samples);
+    Verification::from_samples(holder_samples[0], &holder_samples[1..], &other_samples)
+}
 
-    if override_bal == real_bal {
-        return Err(eyre::eyre!("Balance not updated"));
-    }
-    let update_ratio = utils::ratio_f64(override_bal, new_slot_val, None);
-    
-    Ok(update_ratio)
+async fn sample_balance<P, T>(
+    provider: &P, 
+    ctx: &SearchCtx,
+    locator: &StorageLocator,
+    value: U256,
+) -> Sample 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let balance = balance_with_value(provider, ctx, locator, value).await.ok();
+    Sample { value, balance }
+}
+
+/// Balance reported with the storage behind `locator` set to `value`
+async fn balance_with_value<P, T>(
+    provider: &P, 
+    ctx: &SearchCtx,
+    locator: &StorageLocator,
+    value: U256,
+) -> Result<U256> 
+    where P: Provider<T>, T: Transport + Clone
+{
+    let map_loc = storage_key(provider, ctx, locator).await?;
+    let block = ctx.block.into();
+    let current_word = state::storage_at_with_overrides(
+        provider, 
+        &ctx.overrides, 
+        locator.contract, 
+        map_loc, 
+        block,
+    ).await?;
+    token::call_request_with_storage_overrides(
+        provider,
+        &ctx.call_req(),
+        &ctx.overrides,
+        locator.contract,
+        map_loc,
+        locator.pack(current_word, value)?,
+        block,
+    ).await
 }
 
 
 #[cfg(test)]
 mod tests {
     use alloy::{node_bindings::{Anvil, AnvilInstance}, providers::ReqwestProvider};
+    use crate::{ratio::UpdateRatio, test_utils::{rpc_endpoint, arb_rpc_endpoint}};
     use super::*;
+    use eyre::Result;
 
     pub fn spawn_anvil_provider(fork_url: Option<&str>) -> Result<(ReqwestProvider, AnvilInstance)> {
         let anvil_fork = spawn_anvil(fork_url);
@@ -114,14 +633,40 @@ mod tests {
             None => Anvil::new(),
         }).spawn()
     } 
-    
-    pub fn env_var(var: &str) -> Result<String> {
-        dotenv::dotenv().ok();
-        std::env::var(var).map_err(|_| eyre::eyre!("{} not set", var))
+
+    async fn search_ctx<P, T>(provider: &P, token: Address, holder: Address) -> Result<SearchCtx> 
+        where P: Provider<T>, T: Transport + Clone
+    {
+        let block = provider.get_block_number().await?;
+        Ok(SearchCtx { 
+            token, 
+            holder, 
+            getter: Getter::balance_of(holder),
+            block, 
+            gas_limit: token::CALL_GAS_LIMIT, 
+            verification_value: None,
+            overrides: StateOverride::default(),
+        })
+    }
+
+    async fn best_slot<P, T>(provider: &P, ctx: &SearchCtx, slots: Vec<StorageLocator>) -> Result<StorageLocator> 
+        where P: Provider<T>, T: Transport + Clone
+    {
+        ranked_slots(provider, ctx, slots).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre::eyre!("No valid slots found"))
     }
 
-    fn rpc_endpoint() -> Result<String> {
-        env_var("ETH_RPC_URL")
+    #[test]
+    fn test_failures_beat_not_found() {
+        let transport_err = || Error::Other(eyre::eyre!("connection reset"));
+        let err = more_severe(Some(transport_err()), Error::VerificationFailed);
+        assert!(matches!(err, Error::Other(_)));
+        let err = more_severe(Some(Error::NoPreimageMatch), transport_err());
+        assert!(matches!(err, Error::Other(_)));
+        let err = more_severe(Some(Error::UnknownTokenFamily), Error::NoPreimageMatch);
+        assert!(matches!(err, Error::NoPreimageMatch));
     }
 
     #[tokio::test]
@@ -131,11 +676,12 @@ mod tests {
         let token: Address = "0xC011a73ee8576Fb46F5E1c5751cA3B9Fe0af2a6F".parse().unwrap();
         let holder: Address = "0x1f9090aaE28b8a3dCeaDf281B0F12828e676c326".parse().unwrap();
 
-        let result = find_balance_slots(&provider, holder, token, None).await?;
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
 
         assert_eq!(result.len(), 1);
-        assert_eq!(result[0].0, "0x5b1b5fea1b99d83ad479df0c222f0492385381dd".parse::<Address>().unwrap());
-        assert_eq!(result[0].1, B256::from(U256::from(3)));
+        assert_eq!(result[0].contract, "0x5b1b5fea1b99d83ad479df0c222f0492385381dd".parse::<Address>().unwrap());
+        assert_eq!(result[0].slot, B256::from(U256::from(3)));
         Ok(())
     }
 
@@ -146,17 +692,16 @@ mod tests {
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
         let slot = U256::from(9).into();
         let lang = EvmLanguage::Solidity;
+        let ctx = search_ctx(&provider, token, holder).await?;
 
-        let update_ratio = slot_update_to_bal_ratio(
+        let verification = verify_slot(
             &provider, 
-            token,
-            token,
-            slot, 
-            holder,
-            lang, 
+            &ctx,
+            &StorageLocator::new(token, slot, lang),
         ).await?;
         
-        assert_eq!(update_ratio, 1.0);
+        assert!(verification.update_ratio.is_one());
+        assert_eq!(verification.confidence, 1.0);
         Ok(())
     }
 
@@ -165,20 +710,17 @@ mod tests {
         let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
         let token: Address = "0xfE18be6b3Bd88A2D2A7f928d00292E7a9963CfC6".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
-        let result = find_balance_slots(&provider, holder, token, None).await?;
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
 
         assert_eq!(result.len(), 1);
-        let (contract, slot, lang) = result[0];
-        let update_ratio = slot_update_to_bal_ratio(
+        let verification = verify_slot(
             &provider, 
-            token,
-            contract, 
-            slot, 
-            holder,
-            lang,
+            &ctx,
+            &result[0],
         ).await?;
 
-        assert_eq!(update_ratio, 1.0);
+        assert!(verification.update_ratio.is_one());
         Ok(())
     }
 
@@ -187,20 +729,17 @@ mod tests {
         let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
         let token: Address = "0xC011a73ee8576Fb46F5E1c5751cA3B9Fe0af2a6F".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
-        let result = find_balance_slots(&provider, holder, token, None).await?;
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
 
         assert_eq!(result.len(), 1);
-        let (contract, slot, lang) = result[0];
-        let update_ratio = slot_update_to_bal_ratio(
+        let verification = verify_slot(
             &provider, 
-            token,
-            contract, 
-            slot, 
-            holder,
-            lang,
+            &ctx,
+            &result[0],
         ).await?;
 
-        assert_eq!(update_ratio, 1.0);
+        assert!(verification.update_ratio.is_one());
         Ok(())
     }
 
@@ -209,20 +748,17 @@ mod tests {
         let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
         let token: Address = "0xb8b295df2cd735b15BE5Eb419517Aa626fc43cD5".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
-        let result = find_balance_slots(&provider, holder, token, None).await?;
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
 
         assert_eq!(result.len(), 1);
-        let (contract, slot, lang) = result[0];
-        let ratio = slot_update_to_bal_ratio(
+        let ratio = verify_slot(
             &provider, 
-            token,
-            contract, 
-            slot, 
-            holder, 
-            lang,
-        ).await?;
+            &ctx,
+            &result[0],
+        ).await?.update_ratio;
 
-        assert!(ratio > 1.);
+        assert!(ratio > UpdateRatio::ONE);
         Ok(())
     }
 
@@ -232,18 +768,15 @@ mod tests {
         let token: Address = "0x6c3f90f043a72fa612cbac8115ee7e52bde6e490".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
 
-        let result = find_balance_slots(&provider, holder, token, None).await?;
-        let (contract, slot, lang) = result[0];
-        let ratio = slot_update_to_bal_ratio(
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
+        let ratio = verify_slot(
             &provider, 
-            token,
-            contract, 
-            slot, 
-            holder,
-            lang, 
-        ).await?;
+            &ctx,
+            &result[0],
+        ).await?.update_ratio;
 
-        assert_eq!(ratio, 1.0);
+        assert!(ratio.is_one());
         Ok(())
     }
 
@@ -252,12 +785,13 @@ mod tests {
         let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
         let token: Address = "0xf25c91c87e0b1fd9b4064af0f427157aab0193a7".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
-        let result = find_balance_slots(&provider, holder, token, None).await?;
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
 
-        let (_c, s, r, _l) = closest_slot(&provider, token, holder, result).await?;
+        let locator = best_slot(&provider, &ctx, result).await?;
         
-        assert_eq!(s, B256::from(U256::from(3)));
-        assert_eq!(r, 1.0);
+        assert_eq!(locator.slot, B256::from(U256::from(3)));
+        assert!(locator.update_ratio.is_one());
         Ok(())
     }
 
@@ -266,12 +800,13 @@ mod tests {
         let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
         let token: Address = "0x5f7827fdeb7c20b443265fc2f40845b715385ff2".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
-        let result = find_balance_slots(&provider, holder, token, None).await?;
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
 
-        let (_c, s, r, _l) = closest_slot(&provider, token, holder, result).await?;
+        let locator = best_slot(&provider, &ctx, result).await?;
         
-        assert_eq!(s, B256::from(U256::from(140)));
-        assert_eq!(r, 1.0);
+        assert_eq!(locator.slot, B256::from(U256::from(140)));
+        assert!(locator.update_ratio.is_one());
         Ok(())
     }
 
@@ -280,11 +815,62 @@ mod tests {
         let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
         let token: Address = "0xB8C3B7A2A618C552C23B1E4701109a9E756Bab67".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
-        let result = find_balance_slots(&provider, holder, token, None).await?;
-        let (_c, s, r, _l) = closest_slot(&provider, token, holder, result).await?;
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
+        let locator = best_slot(&provider, &ctx, result).await?;
         
-        assert_eq!(s, B256::from(U256::from(3)));
-        assert_eq!(r, 1.0);
+        assert_eq!(locator.slot, B256::from(U256::from(3)));
+        assert!(locator.update_ratio.is_one());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bal_storage_check_eth_comp_packed() -> Result<()> {
+        let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
+        let token: Address = "0xc00e94Cb662C3520282E6f5717214004A7f26888".parse()?;
+        let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
+        let ctx = search_ctx(&provider, token, holder).await?;
+        let result = find_balance_slots(&provider, &ctx, RpcTracer).await?;
+        let locator = best_slot(&provider, &ctx, result).await?;
+
+        assert_eq!(locator.slot, B256::from(U256::from(1)));
+        assert_eq!((locator.offset, locator.width), (0, 96));
+        assert!(locator.update_ratio.is_one());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_access_list_strategy_eth_usdc() -> Result<()> {
+        let (provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint()?))?;
+        let token: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse()?;
+        let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
+        let search = SlotFinder::new(&provider, token)
+            .with_holder(holder)
+            .with_strategy(Strategy::AccessList)
+            .find()
+            .await?;
+
+        assert_eq!(search.best.contract, token);
+        assert_eq!(search.best.slot, B256::from(U256::from(9)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undeployed_code_on_blank_anvil() -> Result<()> {
+        let (provider, _anvil_instance) = spawn_anvil_provider(None)?;
+        let token = Address::with_last_byte(0xaa);
+        // balanceOf(address) returning `balances[holder]` at slot 0
+        let code = Bytes::from(hex::decode("600435600052600060205260406000205460005260206000f3")?);
+        let search = SlotFinder::new(&provider, token)
+            .with_code(token, code.clone())
+            .with_tracer(crate::RevmTracer)
+            .find()
+            .await?;
+
+        assert_eq!(search.best.contract, token);
+        assert_eq!(search.best.slot, B256::ZERO);
+        assert_eq!(search.best.lang, EvmLanguage::Solidity);
+        assert_eq!(search.best.code_hash, alloy_utils::keccak256(&code));
         Ok(())
     }
 
@@ -292,16 +878,17 @@ mod tests {
 
     #[tokio::test]
     async fn test_bal_storage_check_uni_arbitrum() -> Result<()> {
-        let rpc_endpoint = "https://arb1.arbitrum.io/rpc";
-        let (fork_provider, _anvil_instance) = spawn_anvil_provider(Some(rpc_endpoint))?;
+        let rpc_endpoint = arb_rpc_endpoint();
+        let (fork_provider, _anvil_instance) = spawn_anvil_provider(Some(&rpc_endpoint))?;
         let provider = ReqwestProvider::<Ethereum>::new_http(rpc_endpoint.parse()?);
         let token: Address = "0xfa7f8980b0f1e64a2062791cc3b0871572f1f7f0".parse()?;
         let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
-        let result = find_balance_slots(&fork_provider, holder, token, None).await?;
-        let (_c, s, r, _l) = closest_slot(&provider, token, holder, result).await?;
+        let ctx = search_ctx(&fork_provider, token, holder).await?;
+        let result = find_balance_slots(&fork_provider, &ctx, RpcTracer).await?;
+        let locator = best_slot(&provider, &ctx, result).await?;
         
-        assert_eq!(s, B256::from(U256::from(51)));
-        assert_eq!(r, 1.0);
+        assert_eq!(locator.slot, B256::from(U256::from(51)));
+        assert!(locator.update_ratio.is_one());
         Ok(())
     }
 
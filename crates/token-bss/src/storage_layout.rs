@@ -0,0 +1,236 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use super::{
+    lang::EvmLanguage,
+    locator::StorageLocator,
+    slot_finder::SlotFinder,
+    strategy::Strategy,
+    tracer::Tracer,
+};
+use crate::common::*;
+
+
+// Normalised labels of balance mappings, most specific first
+const BALANCE_LABELS: [&str; 3] = ["balances", "balanceof", "balance"];
+
+/// `storageLayout` output of solc, as found in Foundry artifacts
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageLayout {
+    pub storage: Vec<StorageEntry>,
+    #[serde(default)]
+    pub types: HashMap<String, StorageType>,
+}
+
+/// State variable, or a struct member when nested in a `StorageType`
+#[derive(Debug, Clone, Deserialize)]
+pub struct StorageEntry {
+    pub label: String,
+    /// Byte offset inside the slot, from the lower-order end
+    pub offset: u16,
+    /// Decimal slot index
+    pub slot: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageType {
+    pub encoding: String,
+    pub label: String,
+    pub number_of_bytes: String,
+    pub key: Option<String>,
+    pub value: Option<String>,
+    pub members: Option<Vec<StorageEntry>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FoundryArtifact {
+    storage_layout: StorageLayout,
+}
+
+impl StorageLayout {
+
+    /// Parse a `storageLayout` object, or a Foundry artifact containing one
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(eyre::Report::from)?;
+        let layout = match value.get("storageLayout") {
+            Some(_) => serde_json::from_value::<FoundryArtifact>(value).map(|artifact| artifact.storage_layout),
+            None => serde_json::from_value::<StorageLayout>(value),
+        };
+        Ok(layout.map_err(eyre::Report::from)?)
+    }
+
+    /// Locator of the `_balances`-like mapping (`mapping(address => uint)`, or
+    /// to a struct with a balance member), with its storage in `contract`
+    pub fn balance_locator(&self, contract: Address) -> Result<StorageLocator> {
+        BALANCE_LABELS.iter()
+            .find_map(|label| {
+                self.storage.iter()
+                    .filter(|entry| normalized_label(&entry.label).contains(label))
+                    .find_map(|entry| self.mapping_locator(contract, entry))
+            })
+            .ok_or(Error::NoLayoutMatch)
+    }
+
+    fn mapping_locator(&self, contract: Address, entry: &StorageEntry) -> Option<StorageLocator> {
+        let mapping = self.types.get(&entry.ty).filter(|ty| ty.encoding == "mapping")?;
+        let key = self.types.get(mapping.key.as_ref()?)?;
+        if key.label != "address" && !key.label.starts_with("contract ") {
+            return None;
+        }
+        let slot = B256::from(entry.slot.parse::<U256>().ok()?);
+        let mut locator = StorageLocator::new(contract, slot, EvmLanguage::Solidity);
+        let value_ty = mapping.value.as_ref()?;
+        let value_width = match &self.types.get(value_ty)?.members {
+            Some(members) => {
+                let member = members.iter().find(|member| {
+                    normalized_label(&member.label).contains("balance") && self.is_uint(&member.ty)
+                })?;
+                locator.slot_offset = member.slot.parse().ok()?;
+                locator.offset = member.offset * 8;
+                self.byte_width(&member.ty)?
+            }
+            None if self.is_uint(value_ty) => self.byte_width(value_ty)?,
+            None => return None,
+        };
+        locator.width = value_width * 8;
+        Some(locator)
+    }
+
+    fn byte_width(&self, ty: &str) -> Option<u16> {
+        self.types.get(ty)?.number_of_bytes.parse().ok()
+    }
+
+    fn is_uint(&self, ty: &str) -> bool {
+        self.types.get(ty).map(|ty| ty.label.starts_with("uint")).unwrap_or(false)
+    }
+
+}
+
+fn normalized_label(label: &str) -> String {
+    label.trim_start_matches('_').to_lowercase()
+}
+
+/// Layout locator next to the one found by tracing `balanceOf`
+#[derive(Debug, Clone)]
+pub struct LayoutCheck {
+    pub layout: StorageLocator,
+    pub traced: StorageLocator,
+}
+
+impl LayoutCheck {
+
+    pub fn agrees(&self) -> bool {
+        let location = |l: &StorageLocator| (l.contract, l.slot, l.lang, l.slot_offset, l.offset, l.width);
+        location(&self.layout) == location(&self.traced)
+    }
+
+    /// Warning naming both locations when they disagree
+    pub fn warning(&self) -> Option<String> {
+        if self.agrees() {
+            return None;
+        }
+        let describe = |l: &StorageLocator| format!(
+            "{:?} slot {} ({}, slot offset {}, bits {}..{})",
+            l.contract, U256::from_be_bytes(l.slot.0), l.lang, l.slot_offset, l.offset, l.offset + l.width,
+        );
+        Some(format!(
+            "Storage layout places balances at {}, but the trace found them at {}",
+            describe(&self.layout),
+            describe(&self.traced),
+        ))
+    }
+
+}
+
+/// Resolve the balance locator of `token` from its storage `layout` and
+/// cross-check it against a struct-log search. The layout is taken to
+/// describe the storage of the token itself (eg. a proxy's implementation).
+pub async fn check_storage_layout<P, T>(
+    provider: &P,
+    token: Address,
+    layout: &StorageLayout,
+    tracer: impl Tracer,
+) -> Result<LayoutCheck>
+    where P: Provider<T>, T: Transport + Clone
+{
+    let layout = layout.balance_locator(token)?;
+    let traced = SlotFinder::new(provider, token)
+        .with_strategy(Strategy::StructLogs)
+        .with_tracer(tracer)
+        .find().await?
+        .best;
+    Ok(LayoutCheck { layout, traced })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OZ_LAYOUT: &str = r#"{
+        "storage": [
+            {"astId": 1, "contract": "src/Token.sol:Token", "label": "_owner", "offset": 0, "slot": "0", "type": "t_address"},
+            {"astId": 2, "contract": "src/Token.sol:Token", "label": "_balances", "offset": 0, "slot": "1", "type": "t_mapping(t_address,t_uint256)"},
+            {"astId": 3, "contract": "src/Token.sol:Token", "label": "_allowances", "offset": 0, "slot": "2", "type": "t_mapping(t_address,t_mapping(t_address,t_uint256))"}
+        ],
+        "types": {
+            "t_address": {"encoding": "inplace", "label": "address", "numberOfBytes": "20"},
+            "t_uint256": {"encoding": "inplace", "label": "uint256", "numberOfBytes": "32"},
+            "t_mapping(t_address,t_uint256)": {"encoding": "mapping", "key": "t_address", "label": "mapping(address => uint256)", "numberOfBytes": "32", "value": "t_uint256"},
+            "t_mapping(t_address,t_mapping(t_address,t_uint256))": {"encoding": "mapping", "key": "t_address", "label": "mapping(address => mapping(address => uint256))", "numberOfBytes": "32", "value": "t_mapping(t_address,t_uint256)"}
+        }
+    }"#;
+
+    const STRUCT_ARTIFACT: &str = r#"{
+        "abi": [],
+        "storageLayout": {
+            "storage": [
+                {"astId": 1, "contract": "src/Comp.sol:Comp", "label": "accounts", "offset": 0, "slot": "4", "type": "t_mapping(t_address,t_struct(Account)10_storage)"}
+            ],
+            "types": {
+                "t_address": {"encoding": "inplace", "label": "address", "numberOfBytes": "20"},
+                "t_uint32": {"encoding": "inplace", "label": "uint32", "numberOfBytes": "4"},
+                "t_uint96": {"encoding": "inplace", "label": "uint96", "numberOfBytes": "12"},
+                "t_mapping(t_address,t_struct(Account)10_storage)": {"encoding": "mapping", "key": "t_address", "label": "mapping(address => struct Comp.Account)", "numberOfBytes": "32", "value": "t_struct(Account)10_storage"},
+                "t_struct(Account)10_storage": {"encoding": "inplace", "label": "struct Comp.Account", "numberOfBytes": "64", "members": [
+                    {"astId": 7, "contract": "src/Comp.sol:Comp", "label": "nonce", "offset": 0, "slot": "0", "type": "t_uint32"},
+                    {"astId": 8, "contract": "src/Comp.sol:Comp", "label": "balance", "offset": 4, "slot": "1", "type": "t_uint96"}
+                ]}
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_balance_mapping_from_layout() {
+        let token = Address::with_last_byte(0xaa);
+        let layout = StorageLayout::from_json(OZ_LAYOUT).unwrap();
+        let locator = layout.balance_locator(token).unwrap();
+        assert_eq!(locator, StorageLocator::new(token, B256::from(U256::from(1)), EvmLanguage::Solidity));
+    }
+
+    #[test]
+    fn test_struct_member_from_artifact() {
+        let token = Address::with_last_byte(0xaa);
+        let layout = StorageLayout::from_json(STRUCT_ARTIFACT).unwrap();
+        let locator = layout.balance_locator(token).unwrap();
+        assert_eq!(locator.slot, B256::from(U256::from(4)));
+        assert_eq!(locator.slot_offset, 1);
+        assert_eq!((locator.offset, locator.width), (32, 96));
+    }
+
+    #[test]
+    fn test_disagreement_names_both_locations() {
+        let token = Address::with_last_byte(0xaa);
+        let layout = StorageLocator::new(token, B256::from(U256::from(1)), EvmLanguage::Solidity);
+        let traced = StorageLocator::new(token, B256::from(U256::from(2)), EvmLanguage::Solidity);
+        let check = LayoutCheck { layout: layout.clone(), traced: layout.clone() };
+        assert_eq!(check.warning(), None);
+
+        let warning = LayoutCheck { layout, traced }.warning().unwrap();
+        assert!(warning.contains("slot 1 "));
+        assert!(warning.contains("slot 2 "));
+    }
+
+}
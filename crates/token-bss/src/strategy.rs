@@ -0,0 +1,81 @@
+use serde::{Serialize, Deserialize};
+use super::capabilities::Capabilities;
+
+
+/// How candidate slots are discovered before being verified with state overrides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Strategy {
+    /// Balance location predicted from the layout of a known token 
+    /// implementation, recognised from its runtime code
+    Fingerprint,
+    /// Keccak preimages from struct logs of `debug_traceCall` (or the trace fn)
+    #[default]
+    StructLogs,
+    /// Storage keys from `eth_createAccessList`, matched against mapping 
+    /// locations of low base slots. Works on RPCs without the debug namespace.
+    AccessList,
+    /// Every slot read in the struct logs, overridden in turn. Results are
+    /// raw keys only valid for the traced holder.
+    Poke,
+}
+
+impl Strategy {
+
+    /// Order strategies are tried in when none is chosen explicitly
+    pub const DEFAULT_ORDER: [Strategy; 4] = [
+        Strategy::Fingerprint,
+        Strategy::StructLogs,
+        Strategy::AccessList,
+        Strategy::Poke,
+    ];
+
+    /// Whether the strategy can run against a provider with `capabilities`,
+    /// `local_trace` being set when a trace fn stands in for `debug_traceCall`
+    pub fn is_supported(&self, capabilities: &Capabilities, local_trace: bool) -> bool {
+        capabilities.state_overrides && match self {
+            Strategy::Fingerprint => true,
+            Strategy::StructLogs | Strategy::Poke => capabilities.struct_log_trace || local_trace,
+            Strategy::AccessList => capabilities.access_list,
+        }
+    }
+
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strategy::Fingerprint => write!(f, "fingerprint"),
+            Strategy::StructLogs => write!(f, "structLogs"),
+            Strategy::AccessList => write!(f, "accessList"),
+            Strategy::Poke => write!(f, "poke"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_support_needs_overrides_and_source() {
+        let public_rpc = Capabilities { state_overrides: true, access_list: true, ..Capabilities::default() };
+        let supported: Vec<_> = Strategy::DEFAULT_ORDER.into_iter()
+            .filter(|strategy| strategy.is_supported(&public_rpc, false))
+            .collect();
+        assert_eq!(supported, vec![Strategy::Fingerprint, Strategy::AccessList]);
+        assert!(Strategy::Poke.is_supported(&public_rpc, true));
+
+        let no_overrides = Capabilities { struct_log_trace: true, ..Capabilities::default() };
+        assert!(!Strategy::StructLogs.is_supported(&no_overrides, true));
+    }
+
+    #[test]
+    fn test_display_matches_serde() {
+        for strategy in Strategy::DEFAULT_ORDER {
+            let serialized = serde_json::to_string(&strategy).unwrap();
+            assert_eq!(serialized, format!("\"{strategy}\""));
+        }
+    }
+
+}
@@ -0,0 +1,680 @@
+use std::collections::{HashMap, HashSet};
+use super::trace_parser::TraceEvent;
+use crate::common::*;
+
+
+// Paths started at symbolic branches, on top of the first one
+const MAX_FORKS: usize = 128;
+// Bounds loops on each path
+const MAX_STEPS: usize = 100_000;
+const MAX_MEMORY: usize = 1 << 20;
+const MAX_STACK: usize = 1024;
+const GAS: u64 = 30_000_000;
+// Data external calls are assumed to return, so ABI decoding of it goes through
+const CALL_RETURN_LEN: usize = 32;
+
+/// Word on the stack or in memory, with the bits that depend on symbolic
+/// input (getter args, storage, the environment or external calls) set in
+/// `taint`. The concrete `value` is the witness the path is followed with.
+#[derive(Debug, Clone, Copy, Default)]
+struct Word {
+    value: U256,
+    taint: U256,
+}
+
+impl Word {
+
+    fn concrete(value: U256) -> Self {
+        Self { value, taint: U256::ZERO }
+    }
+
+    fn symbolic(value: U256) -> Self {
+        Self { value, taint: U256::MAX }
+    }
+
+    fn is_symbolic(&self) -> bool {
+        !self.taint.is_zero()
+    }
+
+    // Any bit of the result may depend on any symbolic bit of the operands
+    fn mixed(value: U256, operands: &[Word]) -> Self {
+        match operands.iter().any(Word::is_symbolic) {
+            true => Self::symbolic(value),
+            false => Self::concrete(value),
+        }
+    }
+
+    // Result of a comparison, only its lowest bit can be symbolic
+    fn flag(value: bool, operands: &[Word]) -> Self {
+        let taint = match operands.iter().any(Word::is_symbolic) {
+            true => U256::from(1),
+            false => U256::ZERO,
+        };
+        Self { value: U256::from(value as u8), taint }
+    }
+
+    fn as_usize(&self) -> Option<usize> {
+        usize::try_from(self.value).ok()
+    }
+
+}
+
+/// Byte-addressed memory, with a taint mask per byte
+#[derive(Debug, Clone, Default)]
+struct Memory {
+    data: Vec<u8>,
+    taint: Vec<u8>,
+}
+
+impl Memory {
+
+    fn expand(&mut self, offset: usize, len: usize) -> Option<()> {
+        if len == 0 {
+            return Some(());
+        }
+        let end = offset.checked_add(len)?;
+        if end > MAX_MEMORY {
+            return None;
+        }
+        let end = end.div_ceil(32) * 32;
+        if end > self.data.len() {
+            self.data.resize(end, 0);
+            self.taint.resize(end, 0);
+        }
+        Some(())
+    }
+
+    fn load(&mut self, offset: usize) -> Option<Word> {
+        let (data, taint) = self.read(offset, 32)?;
+        Some(Word { value: U256::from_be_slice(&data), taint: U256::from_be_slice(&taint) })
+    }
+
+    fn store(&mut self, offset: usize, word: Word) -> Option<()> {
+        self.write(offset, &word.value.to_be_bytes::<32>(), &word.taint.to_be_bytes::<32>())
+    }
+
+    fn store_byte(&mut self, offset: usize, word: Word) -> Option<()> {
+        self.write(offset, &[word.value.to_be_bytes::<32>()[31]], &[word.taint.to_be_bytes::<32>()[31]])
+    }
+
+    fn read(&mut self, offset: usize, len: usize) -> Option<(Vec<u8>, Vec<u8>)> {
+        if len == 0 {
+            return Some((Vec::new(), Vec::new()));
+        }
+        self.expand(offset, len)?;
+        let range = offset..offset + len;
+        Some((self.data[range.clone()].to_vec(), self.taint[range].to_vec()))
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8], taint: &[u8]) -> Option<()> {
+        if data.is_empty() {
+            return Some(());
+        }
+        self.expand(offset, data.len())?;
+        self.data[offset..offset + data.len()].copy_from_slice(data);
+        self.taint[offset..offset + taint.len()].copy_from_slice(taint);
+        Some(())
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+}
+
+#[derive(Debug, Clone, Default)]
+struct Path {
+    pc: usize,
+    steps: usize,
+    stack: Vec<Word>,
+    memory: Memory,
+    storage: HashMap<U256, Word>,
+    transient: HashMap<U256, Word>,
+    returndata: (Vec<u8>, Vec<u8>),
+    events: Vec<TraceEvent>,
+}
+
+impl Path {
+
+    fn pop(&mut self) -> Option<Word> {
+        self.stack.pop()
+    }
+
+    fn pop_usize(&mut self) -> Option<usize> {
+        self.pop()?.as_usize()
+    }
+
+    fn push(&mut self, word: Word) -> Option<()> {
+        if self.stack.len() >= MAX_STACK {
+            return None;
+        }
+        self.stack.push(word);
+        Some(())
+    }
+
+    fn binary(&mut self, f: impl Fn(U256, U256) -> U256) -> Option<()> {
+        let (a, b) = (self.pop()?, self.pop()?);
+        self.push(Word::mixed(f(a.value, b.value), &[a, b]))
+    }
+
+    fn compare(&mut self, f: impl Fn(U256, U256) -> bool) -> Option<()> {
+        let (a, b) = (self.pop()?, self.pop()?);
+        self.push(Word::flag(f(a.value, b.value), &[a, b]))
+    }
+
+    // Copies `len` bytes at `offset` of `source`, zero-padded past its end
+    fn copy_to_memory(&mut self, dest: usize, source: &(Vec<u8>, Vec<u8>), offset: U256, len: usize) -> Option<()> {
+        self.memory.expand(dest, len)?;
+        let (data, taint) = (padded(&source.0, offset, len), padded(&source.1, offset, len));
+        self.memory.write(dest, &data, &taint)
+    }
+
+}
+
+enum Step {
+    Next,
+    /// Symbolic branch, the path not taken with the concrete witness
+    Fork(Box<Path>),
+    Return,
+    Halt,
+}
+
+/// Explores the paths of a call to runtime `code`, with everything in
+/// `calldata` after the selector as symbolic input. Branches on symbolic
+/// words are followed both ways, the concretely taken side first. Storage
+/// starts unknown: each unwritten slot loads a symbolic non-zero stand-in.
+/// External calls succeed or fail symbolically and return symbolic data,
+/// their code is never run.
+///
+/// Returns the keccak preimages and SLOADs that depend on the input, along
+/// every path that returns, in path order.
+pub(crate) fn explore(
+    code: &[u8],
+    contract: Address,
+    caller: Address,
+    calldata: &[u8],
+) -> Result<Vec<TraceEvent>> {
+    let program = Program::new(code, contract, caller, calldata);
+    let mut pending = vec![Path::default()];
+    let mut forks = 0;
+    let mut returned = false;
+    let mut events = Vec::new();
+    while let Some(mut path) = pending.pop() {
+        loop {
+            match program.step(&mut path) {
+                Step::Next => (),
+                Step::Fork(other) => {
+                    if forks < MAX_FORKS {
+                        forks += 1;
+                        pending.push(*other);
+                    }
+                },
+                Step::Return => {
+                    returned = true;
+                    events.append(&mut path.events);
+                    break;
+                },
+                Step::Halt => break,
+            }
+        }
+    }
+    if !returned {
+        return Err(Error::TraceCallFailed("No explored path returns".to_string()));
+    }
+    Ok(events)
+}
+
+struct Program<'a> {
+    code: &'a [u8],
+    jumpdests: HashSet<usize>,
+    contract: Address,
+    caller: Address,
+    calldata: (Vec<u8>, Vec<u8>),
+}
+
+impl<'a> Program<'a> {
+
+    fn new(code: &'a [u8], contract: Address, caller: Address, calldata: &[u8]) -> Self {
+        let taint = (0..calldata.len())
+            .map(|idx| if idx < 4 { 0 } else { u8::MAX })
+            .collect();
+        Self {
+            code,
+            jumpdests: jumpdests(code),
+            contract,
+            caller,
+            calldata: (calldata.to_vec(), taint),
+        }
+    }
+
+    fn step(&self, path: &mut Path) -> Step {
+        self.exec(path).unwrap_or(Step::Halt)
+    }
+
+    // `None` aborts the path, eg. on a stack underflow or an invalid jump
+    fn exec(&self, path: &mut Path) -> Option<Step> {
+        path.steps += 1;
+        if path.steps > MAX_STEPS {
+            return None;
+        }
+        let pc = path.pc;
+        let op = self.code.get(pc).copied().unwrap_or(0x00);
+        path.pc += 1;
+        match op {
+            0x00 => return Some(Step::Halt),
+            0x01 => path.binary(|a, b| a.wrapping_add(b))?,
+            0x02 => {
+                let (a, b) = (path.pop()?, path.pop()?);
+                let value = a.value.wrapping_mul(b.value);
+                let word = match (a.is_symbolic(), b.is_symbolic()) {
+                    (false, true) if a.value.is_power_of_two() => shifted_left(b, value, a.value.trailing_zeros()),
+                    (true, false) if b.value.is_power_of_two() => shifted_left(a, value, b.value.trailing_zeros()),
+                    _ => Word::mixed(value, &[a, b]),
+                };
+                path.push(word)?
+            },
+            0x03 => path.binary(|a, b| a.wrapping_sub(b))?,
+            0x04 => {
+                let (a, b) = (path.pop()?, path.pop()?);
+                let value = a.value.checked_div(b.value).unwrap_or_default();
+                let word = match b.is_symbolic() {
+                    false if b.value.is_power_of_two() => {
+                        Word { value, taint: a.taint >> b.value.trailing_zeros() }
+                    },
+                    _ => Word::mixed(value, &[a, b]),
+                };
+                path.push(word)?
+            },
+            0x05 => path.binary(sdiv)?,
+            0x06 => path.binary(|a, b| a.checked_rem(b).unwrap_or_default())?,
+            0x07 => path.binary(smod)?,
+            0x08 | 0x09 => {
+                let (a, b, n) = (path.pop()?, path.pop()?, path.pop()?);
+                let value = match op {
+                    0x08 => a.value.add_mod(b.value, n.value),
+                    _ => a.value.mul_mod(b.value, n.value),
+                };
+                path.push(Word::mixed(value, &[a, b, n]))?
+            },
+            0x0a => path.binary(|a, b| a.wrapping_pow(b))?,
+            0x0b => path.binary(signextend)?,
+            0x10 => path.compare(|a, b| a < b)?,
+            0x11 => path.compare(|a, b| a > b)?,
+            0x12 => path.compare(slt)?,
+            0x13 => path.compare(|a, b| slt(b, a))?,
+            0x14 => path.compare(|a, b| a == b)?,
+            0x15 => {
+                let a = path.pop()?;
+                path.push(Word::flag(a.value.is_zero(), &[a]))?
+            },
+            0x16 => {
+                let (a, b) = (path.pop()?, path.pop()?);
+                // Masking with a constant clears the taint of the masked-out bits
+                let taint = match (a.is_symbolic(), b.is_symbolic()) {
+                    (false, _) => b.taint & a.value,
+                    (_, false) => a.taint & b.value,
+                    _ => a.taint | b.taint,
+                };
+                path.push(Word { value: a.value & b.value, taint })?
+            },
+            0x17 => {
+                let (a, b) = (path.pop()?, path.pop()?);
+                let taint = match (a.is_symbolic(), b.is_symbolic()) {
+                    (false, _) => b.taint & !a.value,
+                    (_, false) => a.taint & !b.value,
+                    _ => a.taint | b.taint,
+                };
+                path.push(Word { value: a.value | b.value, taint })?
+            },
+            0x18 => {
+                let (a, b) = (path.pop()?, path.pop()?);
+                path.push(Word { value: a.value ^ b.value, taint: a.taint | b.taint })?
+            },
+            0x19 => {
+                let a = path.pop()?;
+                path.push(Word { value: !a.value, taint: a.taint })?
+            },
+            0x1a => {
+                let (idx, x) = (path.pop()?, path.pop()?);
+                let byte = |word: U256| match idx.as_usize() {
+                    Some(idx) if idx < 32 => U256::from(word.to_be_bytes::<32>()[idx]),
+                    _ => U256::ZERO,
+                };
+                let word = match idx.is_symbolic() {
+                    true => Word::symbolic(byte(x.value)),
+                    false => Word { value: byte(x.value), taint: byte(x.taint) },
+                };
+                path.push(word)?
+            },
+            0x1b | 0x1c | 0x1d => {
+                let (shift, x) = (path.pop()?, path.pop()?);
+                let value = shift_op(op, shift.value, x.value);
+                let word = match shift.is_symbolic() {
+                    true => Word::mixed(value, &[shift, x]),
+                    // Bits shifted in by SAR copy the sign bit
+                    false if op == 0x1d && x.taint.bit(255) => Word::symbolic(value),
+                    false => Word { value, taint: shift_op(op, shift.value, x.taint) },
+                };
+                path.push(word)?
+            },
+            0x20 => {
+                let (offset, len) = (path.pop_usize()?, path.pop_usize()?);
+                let (preimage, taint) = path.memory.read(offset, len)?;
+                let hash = U256::from_be_bytes(alloy_utils::keccak256(&preimage).0);
+                let symbolic = taint.iter().any(|byte| *byte != 0);
+                if symbolic {
+                    path.events.push(TraceEvent::Keccak(preimage));
+                }
+                path.push(if symbolic { Word::symbolic(hash) } else { Word::concrete(hash) })?
+            },
+            0x30 => path.push(Word::concrete(self.contract.into_word().into()))?,
+            0x31 | 0x3b | 0x3f => {
+                path.pop()?;
+                path.push(Word::symbolic(U256::from(1)))?
+            },
+            0x32 | 0x33 => path.push(Word::concrete(self.caller.into_word().into()))?,
+            0x34 | 0x3a | 0x48 => path.push(Word::concrete(U256::ZERO))?,
+            0x35 => {
+                let offset = path.pop()?.value;
+                let data = padded(&self.calldata.0, offset, 32);
+                let taint = padded(&self.calldata.1, offset, 32);
+                path.push(Word { value: U256::from_be_slice(&data), taint: U256::from_be_slice(&taint) })?
+            },
+            0x36 => path.push(Word::concrete(U256::from(self.calldata.0.len())))?,
+            0x37 | 0x39 | 0x3e => {
+                let (dest, offset, len) = (path.pop_usize()?, path.pop()?.value, path.pop_usize()?);
+                let source = match op {
+                    0x37 => self.calldata.clone(),
+                    0x39 => (self.code.to_vec(), vec![0; self.code.len()]),
+                    _ => {
+                        let end = offset.checked_add(U256::from(len))?;
+                        if end > U256::from(path.returndata.0.len()) {
+                            return None;
+                        }
+                        path.returndata.clone()
+                    },
+                };
+                path.copy_to_memory(dest, &source, offset, len)?
+            },
+            0x38 => path.push(Word::concrete(U256::from(self.code.len())))?,
+            0x3c => {
+                path.pop()?;
+                let (dest, _, len) = (path.pop_usize()?, path.pop()?, path.pop_usize()?);
+                path.memory.expand(dest, len)?;
+                path.memory.write(dest, &vec![0; len], &vec![u8::MAX; len])?
+            },
+            0x3d => path.push(Word::concrete(U256::from(path.returndata.0.len())))?,
+            0x40 | 0x49 => {
+                path.pop()?;
+                path.push(Word::symbolic(U256::ZERO))?
+            },
+            0x41 | 0x44 | 0x47 | 0x4a => path.push(Word::symbolic(U256::ZERO))?,
+            0x42 | 0x43 | 0x46 => path.push(Word::symbolic(U256::from(1)))?,
+            0x45 | 0x5a => path.push(Word::concrete(U256::from(GAS)))?,
+            0x50 => {
+                path.pop()?;
+            },
+            0x51 => {
+                let offset = path.pop_usize()?;
+                let word = path.memory.load(offset)?;
+                path.push(word)?
+            },
+            0x52 | 0x53 => {
+                let (offset, word) = (path.pop_usize()?, path.pop()?);
+                match op {
+                    0x52 => path.memory.store(offset, word)?,
+                    _ => path.memory.store_byte(offset, word)?,
+                }
+            },
+            0x54 => {
+                let slot = path.pop()?;
+                let word = path.storage.get(&slot.value).copied()
+                    .unwrap_or_else(|| Word::symbolic(storage_stand_in(slot.value)));
+                if slot.is_symbolic() {
+                    path.events.push(TraceEvent::Sload {
+                        contract: self.contract,
+                        slot: slot.value.into(),
+                        value: word.value.into(),
+                    });
+                }
+                path.push(word)?
+            },
+            0x55 | 0x5d => {
+                let (slot, word) = (path.pop()?, path.pop()?);
+                match op {
+                    0x55 => path.storage.insert(slot.value, word),
+                    _ => path.transient.insert(slot.value, word),
+                };
+            },
+            0x56 => {
+                let dest = path.pop_usize()?;
+                path.pc = self.jump_target(dest)?;
+            },
+            0x57 => {
+                let (dest, cond) = (path.pop()?, path.pop()?);
+                let target = dest.as_usize().and_then(|dest| self.jump_target(dest));
+                let taken = !cond.value.is_zero();
+                if !cond.is_symbolic() {
+                    if taken {
+                        path.pc = target?;
+                    }
+                    return Some(Step::Next);
+                }
+                // With an invalid target, falling through is the only way on
+                let Some(target) = target else {
+                    return Some(Step::Next);
+                };
+                let mut other = path.clone();
+                match taken {
+                    true => path.pc = target,
+                    false => other.pc = target,
+                }
+                return Some(Step::Fork(Box::new(other)));
+            },
+            0x58 => path.push(Word::concrete(U256::from(pc)))?,
+            0x59 => path.push(Word::concrete(U256::from(path.memory.size())))?,
+            0x5b => (),
+            0x5c => {
+                let slot = path.pop()?;
+                let word = path.transient.get(&slot.value).copied().unwrap_or_default();
+                path.push(word)?
+            },
+            0x5e => {
+                let (dest, offset, len) = (path.pop_usize()?, path.pop_usize()?, path.pop_usize()?);
+                let (data, taint) = path.memory.read(offset, len)?;
+                path.memory.write(dest, &data, &taint)?
+            },
+            0x5f..=0x7f => {
+                let len = (op - 0x5f) as usize;
+                let start = path.pc.min(self.code.len());
+                let end = (path.pc + len).min(self.code.len());
+                let mut bytes = self.code[start..end].to_vec();
+                bytes.resize(len, 0);
+                path.pc += len;
+                path.push(Word::concrete(U256::from_be_slice(&bytes)))?
+            },
+            0x80..=0x8f => {
+                let depth = (op - 0x7f) as usize;
+                let word = *path.stack.get(path.stack.len().checked_sub(depth)?)?;
+                path.push(word)?
+            },
+            0x90..=0x9f => {
+                let depth = (op - 0x8f) as usize;
+                let top = path.stack.len().checked_sub(1)?;
+                let other = top.checked_sub(depth)?;
+                path.stack.swap(top, other);
+            },
+            0xa0..=0xa4 => {
+                for _ in 0..2 + (op - 0xa0) {
+                    path.pop()?;
+                }
+            },
+            0xf1 | 0xf2 | 0xf4 | 0xfa => {
+                let args = match op {
+                    0xf1 | 0xf2 => 7,
+                    _ => 6,
+                };
+                let mut popped = Vec::with_capacity(args);
+                for _ in 0..args {
+                    popped.push(path.pop()?);
+                }
+                path.returndata = (vec![0; CALL_RETURN_LEN], vec![u8::MAX; CALL_RETURN_LEN]);
+                let out_len = popped[args - 1].as_usize()?.min(CALL_RETURN_LEN);
+                if out_len > 0 {
+                    let out_offset = popped[args - 2].as_usize()?;
+                    let returndata = path.returndata.clone();
+                    path.copy_to_memory(out_offset, &returndata, U256::ZERO, out_len)?;
+                }
+                path.push(Word::symbolic(U256::from(1)))?
+            },
+            0xf3 => return Some(Step::Return),
+            // Reverts, creations, self-destructs and invalid opcodes end the path
+            _ => return Some(Step::Halt),
+        }
+        Some(Step::Next)
+    }
+
+    fn jump_target(&self, dest: usize) -> Option<usize> {
+        self.jumpdests.contains(&dest).then_some(dest)
+    }
+
+}
+
+// Offsets of JUMPDESTs outside of push data
+fn jumpdests(code: &[u8]) -> HashSet<usize> {
+    let mut jumpdests = HashSet::new();
+    let mut pc = 0;
+    while let Some(&op) = code.get(pc) {
+        match op {
+            0x5b => {
+                jumpdests.insert(pc);
+            },
+            0x60..=0x7f => pc += (op - 0x5f) as usize,
+            _ => (),
+        }
+        pc += 1;
+    }
+    jumpdests
+}
+
+// Loaded for storage never written on the path. Non-zero, so ids read from
+// storage (eg. `balances[ids[holder]]`) are seen as set, and small enough
+// not to blow up memory when used as a length.
+fn storage_stand_in(slot: U256) -> U256 {
+    let hash = alloy_utils::keccak256(slot.to_be_bytes::<32>());
+    U256::from(u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]) | 1)
+}
+
+fn padded(source: &[u8], offset: U256, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    if let Ok(offset) = usize::try_from(offset) {
+        if offset < source.len() {
+            let available = (source.len() - offset).min(len);
+            bytes[..available].copy_from_slice(&source[offset..offset + available]);
+        }
+    }
+    bytes
+}
+
+fn shifted_left(word: Word, value: U256, shift: usize) -> Word {
+    Word { value, taint: word.taint << shift }
+}
+
+fn shift_op(op: u8, shift: U256, x: U256) -> U256 {
+    let Some(shift) = usize::try_from(shift).ok().filter(|shift| *shift < 256) else {
+        return match op == 0x1d && x.bit(255) {
+            true => U256::MAX,
+            false => U256::ZERO,
+        };
+    };
+    match op {
+        0x1b => x << shift,
+        0x1c => x >> shift,
+        _ if x.bit(255) => !(!x >> shift),
+        _ => x >> shift,
+    }
+}
+
+fn is_negative(x: U256) -> bool {
+    x.bit(255)
+}
+
+fn abs(x: U256) -> U256 {
+    match is_negative(x) {
+        true => x.wrapping_neg(),
+        false => x,
+    }
+}
+
+fn slt(a: U256, b: U256) -> bool {
+    match (is_negative(a), is_negative(b)) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => a < b,
+    }
+}
+
+fn sdiv(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return U256::ZERO;
+    }
+    let quotient = abs(a) / abs(b);
+    match is_negative(a) != is_negative(b) {
+        true => quotient.wrapping_neg(),
+        false => quotient,
+    }
+}
+
+fn smod(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return U256::ZERO;
+    }
+    let rem = abs(a) % abs(b);
+    match is_negative(a) {
+        true => rem.wrapping_neg(),
+        false => rem,
+    }
+}
+
+fn signextend(size: U256, x: U256) -> U256 {
+    let Some(size) = usize::try_from(size).ok().filter(|size| *size < 31) else {
+        return x;
+    };
+    let sign_bit = size * 8 + 7;
+    let mask = (U256::from(1) << (sign_bit + 1)) - U256::from(1);
+    match x.bit(sign_bit) {
+        true => x | !mask,
+        false => x & mask,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_ops() {
+        let neg = |x: u64| U256::from(x).wrapping_neg();
+        assert_eq!(sdiv(neg(7), U256::from(2)), neg(3));
+        assert_eq!(smod(neg(7), U256::from(2)), neg(1));
+        assert!(slt(neg(1), U256::ZERO));
+        assert_eq!(signextend(U256::ZERO, U256::from(0xff)), U256::MAX);
+        assert_eq!(shift_op(0x1d, U256::from(4), neg(32)), neg(2));
+    }
+
+    #[test]
+    fn test_storage_branch_is_explored_both_ways() {
+        // Reverts if the flag at slot 0 is set, as it is with the stand-in value:
+        // PUSH1 0 SLOAD PUSH1 0x12 JUMPI PUSH1 0x04 CALLDATALOAD SLOAD PUSH1 0 MSTORE 
+        // PUSH1 0x20 PUSH1 0 RETURN JUMPDEST PUSH1 0 DUP1 REVERT
+        let code = hex::decode("6000546012576004355460005260206000f35b600080fd").unwrap();
+        let holder = U256::MAX;
+        let calldata = [&[0u8; 4][..], &holder.to_be_bytes::<32>()].concat();
+
+        let events = explore(&code, Address::ZERO, Address::ZERO, &calldata).unwrap();
+
+        // Only the load keyed by the holder depends on the input
+        assert!(matches!(events.as_slice(), [TraceEvent::Sload { slot, .. }] if *slot == B256::from(holder)));
+    }
+
+}
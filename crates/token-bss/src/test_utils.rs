@@ -0,0 +1,14 @@
+pub fn env_var(var: &str) -> eyre::Result<String> {
+    dotenv::dotenv().ok();
+    std::env::var(var).map_err(|_| eyre::eyre!("{} not set", var))
+}
+
+/// Ethereum mainnet endpoint to fork from
+pub fn rpc_endpoint() -> eyre::Result<String> {
+    env_var("ETH_RPC_URL")
+}
+
+/// Arbitrum One endpoint to fork from, the public RPC unless `ARB_RPC_URL` is set
+pub fn arb_rpc_endpoint() -> String {
+    env_var("ARB_RPC_URL").unwrap_or_else(|_| "https://arb1.arbitrum.io/rpc".to_string())
+}
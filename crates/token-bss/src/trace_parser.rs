@@ -1,99 +1,430 @@
 use alloy::rpc::types::trace::geth::StructLog;
 use std::collections::{HashMap, HashSet};
-use super::lang::EvmLanguage;
+use super::{lang::EvmLanguage, locator::{StorageLocator, IndirectKey}};
 use crate::common::*;
 
 
+// Max distance of a struct member from the start of the struct in a mapping
+const MAX_SLOT_OFFSET: u64 = 16;
+// Longest keccak preimage considered as a mapping location
+const MAX_PREIMAGE_LEN: usize = 128;
+// Keys used directly as locations have to be above any regular slot index
+const MIN_UNHASHED_KEY: U256 = U256::from_limbs([u64::MAX, 0, 0, 0]);
+// Max number of SLOADed slots poked
+const MAX_POKE_CANDIDATES: usize = 64;
+
+type SlotMatch = (B256, EvmLanguage, Option<IndirectKey>);
+type Candidate = (Address, B256, EvmLanguage, u64, Option<IndirectKey>);
+
+/// Step of a traced call relevant to locating storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// Preimage hashed by a `KECCAK256`
+    Keccak(Vec<u8>),
+    /// `SLOAD` of `slot` from the storage of `contract`, returning `value`
+    Sload { contract: Address, slot: B256, value: B256 },
+}
+
+impl TraceEvent {
+
+    /// Events of a struct-log trace of a call to `token`
+    pub fn from_struct_logs(struct_logs: Vec<StructLog>, token: Address) -> Vec<TraceEvent> {
+        let mut depth_to_address = HashMap::from([(1, token)]);
+        let mut events = Vec::new();
+        let mut logs = struct_logs.into_iter().peekable();
+        // Malformed logs are skipped instead of failing the whole trace
+        while let Some(log) = logs.next() {
+            let depth = log.depth as usize;
+            let event = match log.op.as_str() {
+                "SLOAD" => sload_event(&log, logs.peek(), depth_to_address.get(&depth)),
+                "KECCAK256" => keccak_event(&log),
+                "STATICCALL" | "CALL" => {
+                    if let Some(address) = call_target(&log) {
+                        depth_to_address.insert(depth + 1, address);
+                    }
+                    None
+                },
+                "DELEGATECALL" => {
+                    if let Some(address) = depth_to_address.get(&depth).copied() {
+                        depth_to_address.insert(depth + 1, address);
+                    }
+                    None
+                },
+                _ => None,
+            };
+            events.extend(event);
+        }
+        events
+    }
+
+}
+
+// The value loaded by an SLOAD is on top of the stack of the following step
+fn sload_event(log: &StructLog, next: Option<&StructLog>, contract: Option<&Address>) -> Option<TraceEvent> {
+    let slot = *log.stack.as_ref()?.last()?;
+    let value = next
+        .and_then(|next| next.stack.as_ref()?.last().copied())
+        .unwrap_or_default();
+    Some(TraceEvent::Sload { contract: *contract?, slot: slot.into(), value: value.into() })
+}
+
+fn keccak_event(log: &StructLog) -> Option<TraceEvent> {
+    let memory = hex::decode(log.memory.as_ref()?.join("")).ok()?;
+    let stack = log.stack.as_ref()?;
+    let mem_offset = usize::try_from(stack.get(stack.len().checked_sub(1)?)?).ok()?;
+    let mem_length = usize::try_from(stack.get(stack.len().checked_sub(2)?)?).ok()?;
+    let preimage = memory.get(mem_offset..mem_offset.checked_add(mem_length)?)?;
+    Some(TraceEvent::Keccak(preimage.to_vec()))
+}
+
+fn call_target(log: &StructLog) -> Option<Address> {
+    let stack = log.stack.as_ref()?;
+    let address = stack.get(stack.len().checked_sub(2)?)?.to_be_bytes::<32>();
+    Some(Address::from_word(address.into()))
+}
+
 #[derive(Default)]
 pub struct TraceParser {
-    depth_to_address: HashMap<usize, Address>,
-    hashed_vals: HashMap<B256, (B256, B256)>,
-    results: HashSet<(Address, B256, EvmLanguage)>,
-    holder: Address,
+    hashed_vals: HashMap<B256, Vec<u8>>,
+    // Non-zero values returned by SLOADs, mapped to the contract and location read
+    sload_vals: HashMap<B256, (Address, B256)>,
+    // Every slot read, in order of first access
+    sloads: Vec<(Address, B256)>,
+    results: HashSet<Candidate>,
+    keys: Vec<B256>,
 }
 
 impl TraceParser {
 
-    pub fn parse(struct_logs: Vec<StructLog>, token: Address, holder: Address) -> Result<Vec<(Address, B256, EvmLanguage)>> {
+    /// Storage candidates read through mappings keyed by `keys` 
+    /// (outermost first, eg. `[owner, spender]` for allowances)
+    pub fn parse(struct_logs: Vec<StructLog>, token: Address, keys: &[B256]) -> Result<Vec<StorageLocator>> {
+        Self::parse_events(TraceEvent::from_struct_logs(struct_logs, token), keys)
+    }
+
+    /// Same as [`TraceParser::parse`], for events of any tracer
+    pub fn parse_events(events: Vec<TraceEvent>, keys: &[B256]) -> Result<Vec<StorageLocator>> {
+        Self::parsed(events, keys).into_candidates()
+    }
+
+    /// Every SLOADed slot as a raw candidate, only valid for `keys`
+    pub fn parse_sloads(
+        struct_logs: Vec<StructLog>, 
+        token: Address, 
+        keys: &[B256],
+    ) -> Result<Vec<StorageLocator>> {
+        Self::parse_event_sloads(TraceEvent::from_struct_logs(struct_logs, token), keys)
+    }
+
+    /// Same as [`TraceParser::parse_sloads`], for events of any tracer
+    pub fn parse_event_sloads(events: Vec<TraceEvent>, keys: &[B256]) -> Result<Vec<StorageLocator>> {
+        let parser = Self::parsed(events, keys);
+        let candidates: Vec<_> = parser.sloads.iter()
+            .take(MAX_POKE_CANDIDATES)
+            .map(|(contract, key)| StorageLocator::raw(*contract, *key, keys))
+            .collect();
+        if candidates.is_empty() {
+            return Err(Error::NoPreimageMatch);
+        }
+        Ok(candidates)
+    }
+
+    fn parsed(events: Vec<TraceEvent>, keys: &[B256]) -> Self {
         let mut parser = TraceParser::default();
-        parser.set_holder(holder);
-        parser.depth_to_address.insert(1, token);
-        parser.parse_logs(struct_logs)?;
-        Ok(parser.results.into_iter().collect())
+        parser.set_keys(keys);
+        for event in events {
+            match event {
+                TraceEvent::Keccak(preimage) => parser.parse_sha3(preimage),
+                TraceEvent::Sload { contract, slot, value } => parser.parse_sload(contract, slot, value),
+            }
+        }
+        parser
+    }
+
+    fn into_candidates(self) -> Result<Vec<StorageLocator>> {
+        if self.results.is_empty() {
+            return Err(Error::NoPreimageMatch);
+        }
+        let candidates = self.results.into_iter()
+            .map(|(contract, slot, lang, slot_offset, indirect)| {
+                let mut locator = StorageLocator::new(contract, slot, lang);
+                locator.slot_offset = slot_offset;
+                locator.indirect = indirect;
+                locator
+            })
+            .collect();
+        Ok(candidates)
     }
 
-    fn parse_logs(&mut self, struct_logs: Vec<StructLog>) -> Result<()>   {
-        for log in struct_logs {
-            self.parse_log(log)?;
+    fn parse_sload(&mut self, contract: Address, slot: B256, value: B256) {
+        if !self.sloads.contains(&(contract, slot)) {
+            self.sloads.push((contract, slot));
+        }
+        if let Some(slot_match) = self.match_offset_loc(contract, slot) {
+            self.results.insert(slot_match);
+        }
+        // Recorded after matching, so a location can't be keyed by its own value
+        if !value.is_zero() {
+            self.sload_vals.insert(value, (contract, slot));
         }
-        Ok(())
     }
 
-    fn parse_log(&mut self, log: StructLog) -> Result<()> {
-        let depth = log.depth as usize;
-        match log.op.as_str() {
-            "SLOAD" => self.parse_sload(log, depth)?,
-            "KECCAK256" => self.parse_sha3(log)?,
-            "STATICCALL" | "CALL" => self.parse_call(log, depth)?,
-            "DELEGATECALL" => self.parse_delegatecall(depth)?,
-            _ => (),
-        }   
-        Ok(())
+    // Struct members in a mapping are stored at `keccak(key, slot) + offset`
+    fn match_offset_loc(&self, contract: Address, slot_idx: B256) -> Option<Candidate> {
+        let slot_idx = U256::from_be_bytes(slot_idx.0);
+        (0..=MAX_SLOT_OFFSET).find_map(|offset| {
+            let loc = B256::from(slot_idx.checked_sub(U256::from(offset))?);
+            self.match_loc(contract, loc)
+                .map(|(slot, lang, indirect)| (contract, slot, lang, offset, indirect))
+        })
     }
 
-    fn parse_sload(&mut self, log: StructLog, depth: usize) -> Result<()> {
-        if log.memory.as_ref().map(|m| m.len() < 2).unwrap_or(true) || log.stack.as_ref().is_none() {
-            return Ok(());
+    fn match_loc(&self, contract: Address, loc: B256) -> Option<SlotMatch> {
+        EvmLanguage::ALL.into_iter()
+            .find_map(|lang| self.base_slot(loc, &self.keys, lang).map(|slot| (slot, lang, None)))
+            .or_else(|| self.match_indirect(contract, loc))
+    }
+
+    // `loc = balances[ids[keys]]`, with the id returned by an earlier SLOAD of the same contract
+    fn match_indirect(&self, contract: Address, loc: B256) -> Option<SlotMatch> {
+        self.sload_vals.iter()
+            .filter(|(_, (id_contract, _))| *id_contract == contract)
+            .find_map(|(id, (_, id_loc))| {
+                let (slot, lang) = EvmLanguage::ALL.into_iter()
+                    .find_map(|lang| self.base_slot(loc, &[*id], lang).map(|slot| (slot, lang)))?;
+                let indirect = EvmLanguage::ALL.into_iter()
+                    .find_map(|lang| {
+                        self.base_slot(*id_loc, &self.keys, lang)
+                            .map(|slot| IndirectKey { slot, lang })
+                    })?;
+                Some((slot, lang, Some(indirect)))
+            })
+    }
+
+    // Unwinds `loc = hash(key_n, ... hash(key_1, slot))` back to the base slot
+    fn base_slot(&self, loc: B256, keys: &[B256], lang: EvmLanguage) -> Option<B256> {
+        match lang {
+            EvmLanguage::Seeded => EvmLanguage::seed(self.hashed_vals.get(&loc)?, keys),
+            // Small keys (eg. the default holder) would match plain slot indices
+            EvmLanguage::Unhashed => match keys {
+                [key] if *key == loc && U256::from_be_bytes(key.0) > MIN_UNHASHED_KEY => Some(B256::ZERO),
+                _ => None,
+            },
+            _ => {
+                let Some((key, outer_keys)) = keys.split_last() else {
+                    return Some(loc);
+                };
+                let inner_loc = lang.inner_loc(self.hashed_vals.get(&loc)?, key)?;
+                self.base_slot(inner_loc, outer_keys, lang)
+            }
         }
-        let stack = log.stack.as_ref().unwrap();
-        let slot_idx: B256 = stack[stack.len()-1].into();
-        if let Some((hashed_val_0, hashed_val_1)) = self.hashed_vals.get(&slot_idx) {
-            let (slot, lang) = match &self.holder.into_word() {
-                v if *v == *hashed_val_0 => (*hashed_val_1, EvmLanguage::Solidity),
-                v if *v == *hashed_val_1 => (*hashed_val_0, EvmLanguage::Vyper),
-                _ => return Ok(()),
-            };
-            let contract = self.depth_to_address.get(&depth).unwrap();
-            self.results.insert((*contract, slot, lang));
+    }
+
+    fn parse_sha3(&mut self, preimage: Vec<u8>) {
+        // Only concerned about storage mappings
+        if (32..=MAX_PREIMAGE_LEN).contains(&preimage.len()) {
+            let hash = alloy_utils::keccak256(&preimage);
+            self.hashed_vals.insert(hash, preimage);
         }
+    }
 
-        Ok(())
-    }
-
-    fn parse_sha3(&mut self, log: StructLog) -> Result<()> {
-        let memory = hex::decode(log.memory.as_ref()
-            .expect("SHA3 op should have memory content")
-            .join("")
-        )?;
-        let stack = log.stack.as_ref()
-            .expect("SHA3 op should have stack content");
-        let mem_offset = stack[stack.len()-1].to::<usize>();
-        let mem_length = stack[stack.len()-2].to::<usize>();
-        if mem_length == 64 { // Only concerned about storage mappings
-            let hashed_val = memory[mem_offset..mem_offset+mem_length].to_vec();
-            let hash = alloy_utils::keccak256(&hashed_val);
-            let hashed_val_0 = B256::from_slice(&hashed_val[0..32]);
-            let hashed_val_1 = B256::from_slice(&hashed_val[32..64]);
-            self.hashed_vals.insert(hash, (hashed_val_0, hashed_val_1));
+    fn set_keys(&mut self, keys: &[B256]) {
+        self.keys = keys.to_vec();
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn struct_log(op: &str, depth: u64, stack: Option<Vec<U256>>, memory: Option<Vec<String>>) -> StructLog {
+        StructLog {
+            op: op.to_string(),
+            depth,
+            stack,
+            memory,
+            ..StructLog::default()
         }
-        Ok(())
     }
 
-    fn parse_call(&mut self, log: StructLog, depth: usize) -> Result<()> {
-        let stack = log.stack.expect("CALL op should have stack content");
-        let address = stack[stack.len()-2].to::<U160>().into();
-        self.depth_to_address.insert(depth + 1, address);
-        Ok(())
+    fn memory_words(memory: &[u8]) -> Vec<String> {
+        memory.chunks(32).map(hex::encode).collect()
+    }
+
+    #[test]
+    fn test_malformed_logs_do_not_panic() {
+        let token = Address::with_last_byte(0xaa);
+        let holder = Address::with_last_byte(1);
+        let logs = vec![
+            struct_log("KECCAK256", 1, None, None),
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64)]), Some(vec!["zz".to_string()])),
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64), U256::MAX]), Some(vec![])),
+            struct_log("SLOAD", 1, Some(vec![]), Some(vec![String::new(); 2])),
+            struct_log("CALL", 1, Some(vec![U256::from(1)]), None),
+            struct_log("DELEGATECALL", 5, None, None),
+        ];
+        let result = TraceParser::parse(logs, token, &[holder.into_word()]);
+        assert!(matches!(result, Err(Error::NoPreimageMatch)));
     }
 
-    fn parse_delegatecall(&mut self, depth: usize) -> Result<()> {
-        let prev_address = *self.depth_to_address.get(&depth).unwrap();
-        self.depth_to_address.insert(depth + 1, prev_address);
-        Ok(())
+    #[test]
+    fn test_solidity_mapping_match() {
+        let token = Address::with_last_byte(0xaa);
+        let holder = Address::with_last_byte(1);
+        let slot = B256::from(U256::from(3));
+        let memory = vec![hex::encode(holder.into_word()), hex::encode(slot)];
+        let key = EvmLanguage::Solidity.mapping_loc(slot, holder);
+        let logs = vec![
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64), U256::ZERO]), Some(memory.clone())),
+            struct_log("SLOAD", 1, Some(vec![key.into()]), Some(memory)),
+        ];
+        let result = TraceParser::parse(logs, token, &[holder.into_word()]).unwrap();
+        assert_eq!(result, vec![StorageLocator::new(token, slot, EvmLanguage::Solidity)]);
     }
 
-    fn set_holder(&mut self, holder: Address) {
-        self.holder = holder;
+    #[test]
+    fn test_struct_member_offset_match() {
+        let token = Address::with_last_byte(0xaa);
+        let holder = Address::with_last_byte(1);
+        let slot = B256::from(U256::from(7));
+        let memory = vec![hex::encode(slot), hex::encode(holder.into_word())];
+        let key = EvmLanguage::Vyper.struct_field_loc(slot, holder, 2);
+        let logs = vec![
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64), U256::ZERO]), Some(memory.clone())),
+            struct_log("SLOAD", 1, Some(vec![key.into()]), Some(memory)),
+        ];
+        let result = TraceParser::parse(logs, token, &[holder.into_word()]).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].slot, slot);
+        assert_eq!(result[0].lang, EvmLanguage::Vyper);
+        assert_eq!(result[0].slot_offset, 2);
+        assert_eq!(result[0].storage_key(holder), key);
+    }
+
+    #[test]
+    fn test_nested_mapping_match() {
+        let token = Address::with_last_byte(0xaa);
+        let owner = Address::with_last_byte(1).into_word();
+        let spender = Address::with_last_byte(2).into_word();
+        let slot = B256::from(U256::from(10));
+        let inner = EvmLanguage::solidity_mapping_loc(&slot, &owner);
+        let key = EvmLanguage::Solidity.nested_mapping_loc(slot, &[owner, spender]);
+        let memory_0 = vec![hex::encode(owner), hex::encode(slot)];
+        let memory_1 = vec![hex::encode(spender), hex::encode(inner)];
+        let logs = vec![
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64), U256::ZERO]), Some(memory_0)),
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64), U256::ZERO]), Some(memory_1.clone())),
+            struct_log("SLOAD", 1, Some(vec![key.into()]), Some(memory_1)),
+        ];
+
+        let result = TraceParser::parse(logs.clone(), token, &[owner, spender]).unwrap();
+        assert_eq!(result, vec![StorageLocator::new(token, slot, EvmLanguage::Solidity)]);
+        // Keys in the wrong order should not match
+        let result = TraceParser::parse(logs, token, &[spender, owner]);
+        assert!(matches!(result, Err(Error::NoPreimageMatch)));
+    }
+
+    #[test]
+    fn test_seeded_match() {
+        let token = Address::with_last_byte(0xaa);
+        let holder = Address::with_last_byte(1);
+        let seed = B256::from(U256::from(0x87a211a2u64));
+        // Solady: mstore(0x0c, seed), mstore(0x00, owner), keccak256(0x0c, 0x20)
+        let mut memory = [holder.into_word().0, [0u8; 32]].concat();
+        memory[40..44].copy_from_slice(&seed[28..]);
+        let key = EvmLanguage::Seeded.mapping_loc(seed, holder);
+        let logs = vec![
+            struct_log("KECCAK256", 1, Some(vec![U256::from(32), U256::from(12)]), Some(memory_words(&memory))),
+            struct_log("SLOAD", 1, Some(vec![key.into()]), Some(memory_words(&memory))),
+        ];
+        let result = TraceParser::parse(logs, token, &[holder.into_word()]).unwrap();
+        assert_eq!(result, vec![StorageLocator::new(token, seed, EvmLanguage::Seeded)]);
+    }
+
+    #[test]
+    fn test_packed_and_unhashed_match() {
+        let token = Address::with_last_byte(0xaa);
+        let holder: Address = "0xDAFEA492D9c6733ae3d56b7Ed1ADB60692c98Bc5".parse().unwrap();
+        let slot = B256::from(U256::from(5));
+        let memory = [holder.as_slice(), slot.as_slice(), &[0u8; 12]].concat();
+        let packed_key = EvmLanguage::Packed.mapping_loc(slot, holder);
+        let logs = vec![
+            struct_log("KECCAK256", 1, Some(vec![U256::from(52), U256::ZERO]), Some(memory_words(&memory))),
+            struct_log("SLOAD", 1, Some(vec![packed_key.into()]), Some(memory_words(&memory))),
+            struct_log("SLOAD", 1, Some(vec![holder.into_word().into()]), Some(memory_words(&memory))),
+        ];
+        let mut result = TraceParser::parse(logs, token, &[holder.into_word()]).unwrap();
+        result.sort_by_key(|locator| locator.lang.to_string());
+        assert_eq!(result, vec![
+            StorageLocator::new(token, slot, EvmLanguage::Packed),
+            StorageLocator::new(token, B256::ZERO, EvmLanguage::Unhashed),
+        ]);
+        assert_eq!(result[1].storage_key(holder), holder.into_word());
+    }
+
+    #[test]
+    fn test_indirect_key_match() {
+        let token = Address::with_last_byte(0xaa);
+        let holder = Address::with_last_byte(1);
+        let ids_slot = B256::from(U256::from(4));
+        let balances_slot = B256::from(U256::from(5));
+        let id = B256::from(U256::from(42));
+        let id_loc = EvmLanguage::Solidity.mapping_loc(ids_slot, holder);
+        let balance_loc = EvmLanguage::Solidity.nested_mapping_loc(balances_slot, &[id]);
+        let memory_0 = vec![hex::encode(holder.into_word()), hex::encode(ids_slot)];
+        let memory_1 = vec![hex::encode(id), hex::encode(balances_slot)];
+        let logs = vec![
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64), U256::ZERO]), Some(memory_0.clone())),
+            struct_log("SLOAD", 1, Some(vec![id_loc.into()]), Some(memory_0)),
+            struct_log("PUSH1", 1, Some(vec![id.into()]), None),
+            struct_log("KECCAK256", 1, Some(vec![U256::from(64), U256::ZERO]), Some(memory_1.clone())),
+            struct_log("SLOAD", 1, Some(vec![balance_loc.into()]), Some(memory_1)),
+        ];
+        let result = TraceParser::parse(logs, token, &[holder.into_word()]).unwrap();
+        let indirect = result.iter().find(|locator| locator.indirect.is_some()).unwrap();
+
+        assert_eq!(indirect.slot, balances_slot);
+        assert_eq!(indirect.indirect, Some(IndirectKey { slot: ids_slot, lang: EvmLanguage::Solidity }));
+        assert_eq!(result.len(), 2); // `ids[holder]` is a direct candidate too
+    }
+
+    #[test]
+    fn test_struct_log_events_follow_calls() {
+        let token = Address::with_last_byte(0xaa);
+        let storage = Address::with_last_byte(0xbb);
+        let slot = B256::from(U256::from(3));
+        let value = U256::from(42);
+        let logs = vec![
+            struct_log("STATICCALL", 1, Some(vec![storage.into_word().into(), U256::ZERO]), None),
+            struct_log("DELEGATECALL", 2, None, None),
+            struct_log("SLOAD", 3, Some(vec![slot.into()]), None),
+            struct_log("PUSH1", 3, Some(vec![value]), None),
+            struct_log("SLOAD", 1, Some(vec![slot.into()]), None),
+        ];
+        let events = TraceEvent::from_struct_logs(logs, token);
+        assert_eq!(events, vec![
+            TraceEvent::Sload { contract: storage, slot, value: value.into() },
+            TraceEvent::Sload { contract: token, slot, value: B256::ZERO },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_sloads_returns_raw_keys() {
+        let token = Address::with_last_byte(0xaa);
+        let holder = Address::with_last_byte(1);
+        let key = B256::from(U256::from(0xdead));
+        let logs = vec![
+            struct_log("SLOAD", 1, Some(vec![key.into()]), None),
+            struct_log("SLOAD", 1, Some(vec![key.into()]), None),
+        ];
+        let result = TraceParser::parse(logs.clone(), token, &[holder.into_word()]);
+        assert!(matches!(result, Err(Error::NoPreimageMatch)));
+
+        let result = TraceParser::parse_sloads(logs, token, &[holder.into_word()]).unwrap();
+        assert_eq!(result, vec![StorageLocator::raw(token, key, &[holder.into_word()])]);
+        assert_eq!(result[0].storage_key(holder), key);
     }
 
 }
@@ -0,0 +1,197 @@
+use alloy::{
+    providers::ext::DebugApi,
+    transports::RpcError,
+    rpc::types::{
+        eth::{Header, state::StateOverride},
+        trace::geth::{DefaultFrame, GethTrace, GethDebugTracingCallOptions},
+    },
+};
+use std::{future::Future, sync::Arc};
+use super::{ops::trace, trace_parser::TraceEvent};
+use crate::common::*;
+
+
+const METHOD_NOT_FOUND_CODE: i64 = -32601;
+
+/// Source of struct-log traces for a call at a block
+pub trait Tracer: Send + Sync {
+
+    /// Geth-style trace of the call. Tracers that only produce events 
+    /// (see [`Tracer::trace_events`]) can leave this out.
+    fn trace_call<P, T>(
+        &self,
+        _provider: &P,
+        _call_request: TransactionRequest,
+        _block: BlockNumberOrTag,
+        _options: GethDebugTracingCallOptions,
+    ) -> impl Future<Output = Result<GethTrace>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async { Err(Error::TraceUnsupported) }
+    }
+
+    /// SLOADs and keccak preimages of a call to `contract` with `overrides` applied, 
+    /// in execution order. By default they are extracted from the struct logs of 
+    /// [`Tracer::trace_call`].
+    fn trace_events<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        call_request: TransactionRequest,
+        block: BlockNumberOrTag,
+        overrides: StateOverride,
+    ) -> impl Future<Output = Result<Vec<TraceEvent>>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async move {
+            let frame = trace::default_trace_call(provider, self, call_request, Some(block), overrides).await?;
+            Ok(TraceEvent::from_struct_logs(frame.struct_logs, contract))
+        }
+    }
+
+    /// Whether traces come from the provider's `debug_traceCall`, so
+    /// struct-log support depends on the provider's capabilities
+    fn is_rpc(&self) -> bool {
+        false
+    }
+
+}
+
+impl<Tr: Tracer> Tracer for &Tr {
+
+    fn trace_call<P, T>(
+        &self,
+        provider: &P,
+        call_request: TransactionRequest,
+        block: BlockNumberOrTag,
+        options: GethDebugTracingCallOptions,
+    ) -> impl Future<Output = Result<GethTrace>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        (**self).trace_call(provider, call_request, block, options)
+    }
+
+    fn trace_events<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        call_request: TransactionRequest,
+        block: BlockNumberOrTag,
+        overrides: StateOverride,
+    ) -> impl Future<Output = Result<Vec<TraceEvent>>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        (**self).trace_events(provider, contract, call_request, block, overrides)
+    }
+
+    fn is_rpc(&self) -> bool {
+        (**self).is_rpc()
+    }
+
+}
+
+/// Traces with the provider's `debug_traceCall`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RpcTracer;
+
+impl Tracer for RpcTracer {
+
+    fn trace_call<P, T>(
+        &self,
+        provider: &P,
+        call_request: TransactionRequest,
+        block: BlockNumberOrTag,
+        options: GethDebugTracingCallOptions,
+    ) -> impl Future<Output = Result<GethTrace>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async move {
+            provider.debug_trace_call(call_request, block, options).await
+                .map_err(|err| match err {
+                    RpcError::ErrorResp(payload) if payload.code == METHOD_NOT_FOUND_CODE => {
+                        Error::TraceUnsupported
+                    },
+                    err => Error::Provider(err),
+                })
+        }
+    }
+
+    fn is_rpc(&self) -> bool {
+        true
+    }
+
+}
+
+/// Traces with a synchronous local tracer (eg. `poor_mans_tracer::geth_trace_sync`),
+/// run on the blocking thread pool so it doesn't stall the executor
+pub struct LocalTracer<F> {
+    trace_fn: Arc<F>,
+}
+
+impl<F> LocalTracer<F>
+    where F: Fn(TransactionRequest, Header, GethDebugTracingCallOptions) -> eyre::Result<GethTrace>
+        + Send + Sync + 'static
+{
+    pub fn new(trace_fn: F) -> Self {
+        Self { trace_fn: Arc::new(trace_fn) }
+    }
+}
+
+impl<F> Tracer for LocalTracer<F>
+    where F: Fn(TransactionRequest, Header, GethDebugTracingCallOptions) -> eyre::Result<GethTrace>
+        + Send + Sync + 'static
+{
+
+    fn trace_call<P, T>(
+        &self,
+        provider: &P,
+        call_request: TransactionRequest,
+        block: BlockNumberOrTag,
+        options: GethDebugTracingCallOptions,
+    ) -> impl Future<Output = Result<GethTrace>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        let trace_fn = self.trace_fn.clone();
+        async move {
+            let header = provider.get_block(block.into(), false).await?
+                .ok_or_else(|| eyre::eyre!("Block {block:?} not found"))?
+                .header;
+            tokio::task::spawn_blocking(move || trace_fn(call_request, header, options))
+                .await
+                .map_err(|err| Error::TraceCallFailed(err.to_string()))?
+                .map_err(|err| Error::TraceCallFailed(err.to_string()))
+        }
+    }
+
+}
+
+/// Replays a recorded trace for every call, eg. for offline tests
+#[derive(Debug, Clone)]
+pub struct RecordedTracer {
+    trace: DefaultFrame,
+}
+
+impl RecordedTracer {
+
+    pub fn new(trace: DefaultFrame) -> Self {
+        Self { trace }
+    }
+
+}
+
+impl Tracer for RecordedTracer {
+
+    fn trace_call<P, T>(
+        &self,
+        _provider: &P,
+        _call_request: TransactionRequest,
+        _block: BlockNumberOrTag,
+        _options: GethDebugTracingCallOptions,
+    ) -> impl Future<Output = Result<GethTrace>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        let trace = GethTrace::Default(self.trace.clone());
+        async move { Ok(trace) }
+    }
+
+}
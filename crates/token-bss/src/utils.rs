@@ -1,21 +1,6 @@
 use crate::common::*;
 
 
-const DEFAULT_PRECISION_MUL: u128 = 10_000;
-
-pub fn ratio_f64(val1: U256, val2: U256, precision_mul: Option<u128>) -> f64 {
-    if val2 == U256::ZERO {
-        return f64::INFINITY;
-    }
-    let p_mul = precision_mul.unwrap_or(DEFAULT_PRECISION_MUL);
-    let ur_bn = U512::from(val1) * U512::from(p_mul) / U512::from(val2);
-    if ur_bn <= U512::from(U128::MAX) {
-        ur_bn.to::<u128>() as f64 / p_mul as f64
-    } else {
-        f64::INFINITY
-    }
-}
-
 pub fn bytes_to_u256(val: Bytes) -> U256 {
     let bytes = val.to_vec();
     if bytes.is_empty() {
@@ -24,3 +9,48 @@ pub fn bytes_to_u256(val: Bytes) -> U256 {
         B256::from_slice(&bytes[..32]).into()
     }
 }
+
+pub fn field_max(width: u16) -> U256 {
+    if width >= 256 {
+        U256::MAX
+    } else {
+        (U256::from(1) << width as usize) - U256::from(1)
+    }
+}
+
+/// Storage word whose byte `i` (little-endian) holds `i + 1`
+pub fn byte_index_pattern() -> U256 {
+    (0..32usize).fold(U256::ZERO, |acc, i| acc | (U256::from(i + 1) << (8 * i)))
+}
+
+/// Bit offset and width of the field returned after overriding a word 
+/// with `byte_index_pattern`, if it maps to a contiguous byte range
+pub fn bit_range_from_probe(returned: U256) -> Option<(u16, u16)> {
+    let first = returned.byte(0) as usize;
+    if first == 0 || first > 32 {
+        return None;
+    }
+    let start = first - 1;
+    let len = (0..32 - start)
+        .take_while(|&i| returned.byte(i) as usize == start + i + 1)
+        .count();
+    let expected = (byte_index_pattern() >> (8 * start)) & field_max((8 * len) as u16);
+    (expected == returned).then_some(((8 * start) as u16, (8 * len) as u16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bit_range_from_probe() {
+        let pattern = byte_index_pattern();
+        assert_eq!(bit_range_from_probe(pattern), Some((0, 256)));
+        assert_eq!(bit_range_from_probe(pattern & field_max(96)), Some((0, 96)));
+        assert_eq!(bit_range_from_probe((pattern >> 160) & field_max(32)), Some((160, 32)));
+        assert_eq!(bit_range_from_probe(pattern >> 8), Some((8, 248)));
+        assert_eq!(bit_range_from_probe(U256::ZERO), None);
+        assert_eq!(bit_range_from_probe(pattern * U256::from(2)), None);
+    }
+
+}
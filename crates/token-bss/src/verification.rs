@@ -0,0 +1,166 @@
+use std::cmp::Ordering;
+use super::{locator::StorageLocator, ratio::UpdateRatio};
+use crate::common::*;
+
+
+// Relative difference allowed between a balance and the one predicted by the ratio
+const MAX_RATIO_DRIFT_BPS: u64 = 100;
+// Share of probes that have to be consistent for a candidate to be accepted
+const MIN_CONFIDENCE: f64 = 0.5;
+
+/// Outcome of probing a candidate slot with several holders and values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Verification {
+    pub update_ratio: UpdateRatio,
+    pub confidence: f64,
+}
+
+/// Balance reported with `value` written to the candidate slot, `None` if
+/// the call failed (eg. overflowing share math for values close to max)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Sample {
+    pub value: U256,
+    pub balance: Option<U256>,
+}
+
+impl Verification {
+
+    /// Check the samples scale linearly with the `reference` one, taken for
+    /// the traced holder. The slot has to move the reported balance at all,
+    /// so at least one other `holder_samples` value has to differ from it.
+    /// A wrong mapping can still scale for the traced holder, so any balance
+    /// of `other_samples` off the line rejects the candidate outright.
+    pub fn from_samples(
+        reference: Sample,
+        holder_samples: &[Sample],
+        other_samples: &[Sample],
+    ) -> Result<Self> {
+        let ref_balance = reference.balance.ok_or(Error::BalanceNotWritable)?;
+        let writable = holder_samples.iter()
+            .any(|sample| sample.balance.is_some_and(|balance| balance != ref_balance));
+        if reference.value.is_zero() || !writable {
+            return Err(Error::BalanceNotWritable);
+        }
+        let other_mismatch = other_samples.iter()
+            .any(|sample| sample.balance.is_some() && !is_linear(reference.value, ref_balance, sample));
+        if other_mismatch {
+            return Err(Error::BalanceNotWritable);
+        }
+        let samples = holder_samples.iter().chain(other_samples);
+        let consistent = samples.clone()
+            .filter(|sample| is_linear(reference.value, ref_balance, sample))
+            .count();
+        let total = samples.count() + 1;
+        let confidence = (consistent + 1) as f64 / total as f64;
+        if confidence < MIN_CONFIDENCE {
+            return Err(Error::BalanceNotWritable);
+        }
+        let update_ratio = UpdateRatio::new(ref_balance, reference.value)?;
+        Ok(Self { update_ratio, confidence })
+    }
+
+}
+
+// Expected balance is `ref_balance * value / ref_value`, within the drift or
+// the rounding of one stored unit, whichever is larger
+fn is_linear(ref_value: U256, ref_balance: U256, sample: &Sample) -> bool {
+    let Some(balance) = sample.balance else {
+        return false;
+    };
+    let expected = U512::from(ref_balance) * U512::from(sample.value) / U512::from(ref_value);
+    let balance = U512::from(balance);
+    let diff = if balance > expected { balance - expected } else { expected - balance };
+    let rounding = U512::from(ref_balance) / U512::from(ref_value) + U512::from(1);
+    diff <= rounding || diff * U512::from(10_000) <= expected * U512::from(MAX_RATIO_DRIFT_BPS)
+}
+
+/// Values written to a candidate slot, the reference one first: zero, a small
+/// value and one close to the maximum the field can hold follow it
+pub(crate) fn probe_values(reference: Option<U256>, max_value: U256) -> Vec<U256> {
+    let reference = reference.unwrap_or_else(|| U256::from(rand::random::<u128>())) & max_value;
+    let small = U256::from(rand::random::<u16>() % 1000 + 1) & max_value;
+    let near_max = max_value.saturating_sub(U256::from(rand::random::<u32>()));
+    let mut values = vec![reference.max(U256::from(1)), U256::ZERO, small, near_max];
+    let mut seen = Vec::with_capacity(values.len());
+    values.retain(|value| {
+        let new = !seen.contains(value);
+        seen.push(*value);
+        new
+    });
+    values
+}
+
+/// Most confident candidates first, then the closest to a one-to-one update ratio
+pub(crate) fn compare_candidates(a: &StorageLocator, b: &StorageLocator) -> Ordering {
+    b.confidence.total_cmp(&a.confidence)
+        .then_with(|| a.update_ratio.cmp_distance_from_one(&b.update_ratio))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(value: u64, balance: Option<u64>) -> Sample {
+        Sample { value: U256::from(value), balance: balance.map(U256::from) }
+    }
+
+    #[test]
+    fn test_linear_samples_are_confident() {
+        let reference = sample(1_000_000, Some(2_000_000));
+        let holder = [sample(0, Some(0)), sample(7, Some(14))];
+        let others = [sample(1_000_000, Some(2_000_000)), sample(3, Some(6))];
+        let verification = Verification::from_samples(reference, &holder, &others).unwrap();
+        assert_eq!(verification.update_ratio, UpdateRatio::new(U256::from(2), U256::from(1)).unwrap());
+        assert_eq!(verification.confidence, 1.);
+    }
+
+    #[test]
+    fn test_failed_samples_lower_confidence() {
+        let reference = sample(1_000_000, Some(1_000_000));
+        let holder = [sample(0, Some(0)), sample(u64::MAX, None)];
+        let others = [sample(1_000_000, Some(1_000_000)), sample(u64::MAX, None)];
+        let verification = Verification::from_samples(reference, &holder, &others).unwrap();
+        assert_eq!(verification.confidence, 0.6);
+    }
+
+    #[test]
+    fn test_nonlinear_other_holder_is_rejected() {
+        let reference = sample(1_000_000, Some(1_000_000));
+        let holder = [sample(0, Some(0)), sample(7, Some(7))];
+        let others = [sample(1_000_000, Some(5)), sample(7, Some(7))];
+        let result = Verification::from_samples(reference, &holder, &others);
+        assert!(matches!(result, Err(Error::BalanceNotWritable)));
+    }
+
+    #[test]
+    fn test_unresponsive_slot_is_rejected() {
+        let reference = sample(1_000_000, Some(42));
+        let holder = [sample(0, Some(42)), sample(7, Some(42))];
+        let result = Verification::from_samples(reference, &holder, &[]);
+        assert!(matches!(result, Err(Error::BalanceNotWritable)));
+    }
+
+    #[test]
+    fn test_ranking_is_not_saturated() {
+        let locator = |update_ratio: u64, confidence: f64| {
+            let mut locator = StorageLocator::new(Address::ZERO, B256::ZERO, crate::EvmLanguage::Solidity);
+            locator.update_ratio = UpdateRatio::new(U256::from(update_ratio), U256::from(1)).unwrap();
+            locator.confidence = confidence;
+            locator
+        };
+        let mut candidates = vec![locator(10, 1.), locator(4, 1.), locator(1, 0.75)];
+        candidates.sort_by(compare_candidates);
+        let ratios: Vec<_> = candidates.iter().map(|l| l.update_ratio.to_f64()).collect();
+        assert_eq!(ratios, vec![4., 10., 1.]);
+    }
+
+    #[test]
+    fn test_probe_values_fit_field() {
+        let max_value = U256::from(u8::MAX);
+        let values = probe_values(Some(U256::from(0x1234)), max_value);
+        assert_eq!(values[0], U256::from(0x34));
+        assert!(values.contains(&U256::ZERO));
+        assert!(values.iter().all(|value| *value <= max_value));
+    }
+
+}
@@ -0,0 +1,264 @@
+use std::future::Future;
+use crate::common::*;
+
+
+/// Overwrites storage slots on a dev node or a virtual testnet
+pub trait StorageWriter: Send + Sync {
+
+    fn set_storage_at<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        key: B256,
+        value: B256,
+    ) -> impl Future<Output = Result<()>> + Send
+        where P: Provider<T>, T: Transport + Clone;
+
+}
+
+impl<W: StorageWriter> StorageWriter for &W {
+
+    fn set_storage_at<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        key: B256,
+        value: B256,
+    ) -> impl Future<Output = Result<()>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        (**self).set_storage_at(provider, contract, key, value)
+    }
+
+}
+
+/// Writes with Anvil's `anvil_setStorageAt`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnvilStorageWriter;
+
+impl StorageWriter for AnvilStorageWriter {
+
+    fn set_storage_at<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        key: B256,
+        value: B256,
+    ) -> impl Future<Output = Result<()>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async move {
+            let updated = provider.client()
+                .request("anvil_setStorageAt", (contract, key, value))
+                .await?;
+            check_updated(updated, contract)
+        }
+    }
+
+}
+
+/// Writes with Hardhat's `hardhat_setStorageAt`, which takes the slot as a quantity
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HardhatStorageWriter;
+
+impl StorageWriter for HardhatStorageWriter {
+
+    fn set_storage_at<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        key: B256,
+        value: B256,
+    ) -> impl Future<Output = Result<()>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async move {
+            let key = U256::from_be_bytes(key.0);
+            let updated = provider.client()
+                .request("hardhat_setStorageAt", (contract, key, value))
+                .await?;
+            check_updated(updated, contract)
+        }
+    }
+
+}
+
+/// Writes with Ganache's `evm_setAccountStorageAt`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GanacheStorageWriter;
+
+impl StorageWriter for GanacheStorageWriter {
+
+    fn set_storage_at<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        key: B256,
+        value: B256,
+    ) -> impl Future<Output = Result<()>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async move {
+            let updated = provider.client()
+                .request("evm_setAccountStorageAt", (contract, key, value))
+                .await?;
+            check_updated(updated, contract)
+        }
+    }
+
+}
+
+/// Writes with `tenderly_setStorageAt` on Tenderly forks and virtual testnets,
+/// which answer with the hash of the state change rather than a flag
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TenderlyStorageWriter;
+
+impl StorageWriter for TenderlyStorageWriter {
+
+    fn set_storage_at<P, T>(
+        &self,
+        provider: &P,
+        contract: Address,
+        key: B256,
+        value: B256,
+    ) -> impl Future<Output = Result<()>> + Send
+        where P: Provider<T>, T: Transport + Clone
+    {
+        async move {
+            provider.client()
+                .request::<_, serde_json::Value>("tenderly_setStorageAt", (contract, key, value))
+                .await?;
+            Ok(())
+        }
+    }
+
+}
+
+fn check_updated(updated: bool, contract: Address) -> Result<()> {
+    if !updated {
+        return Err(eyre::eyre!("Storage of {contract:?} not updated").into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{node_bindings::Anvil, providers::ReqwestProvider};
+    use serde_json::{json, Value};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::mpsc,
+    };
+    use super::*;
+    use crate::ops::state;
+    use eyre::Result;
+
+    // JSON-RPC stand-in for nodes that can't run locally, answering every
+    // request with `result` and passing the requests on for inspection
+    async fn spawn_stand_in(result: Value) -> Result<(ReqwestProvider, mpsc::UnboundedReceiver<Value>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let (requests_tx, requests_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (requests_tx, result) = (requests_tx.clone(), result.clone());
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    while let Some(body) = read_http_body(&mut stream).await {
+                        let request: Value = serde_json::from_slice(&body).unwrap_or_default();
+                        let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }).to_string();
+                        let _ = requests_tx.send(request);
+                        let http = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{response}",
+                            response.len(),
+                        );
+                        if stream.get_mut().write_all(http.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        Ok((ReqwestProvider::new_http(url.parse()?), requests_rx))
+    }
+
+    async fn read_http_body(stream: &mut BufReader<tokio::net::TcpStream>) -> Option<Vec<u8>> {
+        let mut content_length = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await.ok()? == 0 {
+                return None;
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().ok()?;
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        stream.read_exact(&mut body).await.ok()?;
+        Some(body)
+    }
+
+    #[tokio::test]
+    async fn test_anvil_and_hardhat_writers() -> Result<()> {
+        let anvil = Anvil::new().spawn();
+        let provider = ReqwestProvider::new_http(anvil.endpoint_url());
+        let contract = Address::with_last_byte(0xaa);
+        let block = BlockNumberOrTag::Latest;
+
+        let (key, value) = (B256::with_last_byte(1), B256::repeat_byte(0x11));
+        AnvilStorageWriter.set_storage_at(&provider, contract, key, value).await?;
+        assert_eq!(state::storage_at(&provider, contract, key, block).await?, value);
+
+        // Anvil serves the hardhat namespace as well
+        let (key, value) = (B256::with_last_byte(2), B256::repeat_byte(0x22));
+        HardhatStorageWriter.set_storage_at(&provider, contract, key, value).await?;
+        assert_eq!(state::storage_at(&provider, contract, key, block).await?, value);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ganache_writer() -> Result<()> {
+        let (provider, mut requests) = spawn_stand_in(json!(true)).await?;
+        let contract = Address::with_last_byte(0xaa);
+        let (key, value) = (B256::with_last_byte(1), B256::repeat_byte(0x11));
+
+        GanacheStorageWriter.set_storage_at(&provider, contract, key, value).await?;
+
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request["method"], "evm_setAccountStorageAt");
+        assert_eq!(request["params"], json!([contract, key, value]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tenderly_writer() -> Result<()> {
+        let (provider, mut requests) = spawn_stand_in(json!(B256::repeat_byte(0xab))).await?;
+        let contract = Address::with_last_byte(0xaa);
+        let (key, value) = (B256::with_last_byte(1), B256::repeat_byte(0x11));
+
+        TenderlyStorageWriter.set_storage_at(&provider, contract, key, value).await?;
+
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request["method"], "tenderly_setStorageAt");
+        assert_eq!(request["params"], json!([contract, key, value]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejected_write() -> Result<()> {
+        let (provider, _requests) = spawn_stand_in(json!(false)).await?;
+        let contract = Address::with_last_byte(0xaa);
+        let result = GanacheStorageWriter.set_storage_at(&provider, contract, B256::ZERO, B256::ZERO).await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+}
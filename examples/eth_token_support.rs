@@ -25,8 +25,9 @@ async fn main() -> Result<()> {
     
     for (symbol, token) in ethereum_tokens {
         println!("Checking {symbol}({token:?})");
-        match token_bss::find_slot(&provider, token, None, None).await {
-            Ok((contract, slot, update_ratio, lang)) => {
+        match token_bss::find_slot(&provider, token, None, token_bss::RpcTracer).await {
+            Ok(locator) => {
+                let token_bss::StorageLocator { contract, slot, update_ratio, lang, .. } = locator;
                 println!("{symbol}({token:?}): {contract:?}({lang}) - {slot:?} / ΔR: {update_ratio}")
             }
             Err(e) => println!("{symbol}({token:?}): {e}"),